@@ -0,0 +1,162 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Measures how long it takes to hand events to the listeners of a `Handler`, and to add and
+//! remove listeners.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use async_winit::{Handler, ThreadUnsafe};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures_lite::{future, StreamExt};
+
+/// The numbers of listeners to benchmark with.
+const LISTENERS: [usize; 3] = [1, 4, 16];
+
+/// The numbers of existing listeners to add and remove a listener next to.
+const SUBSCRIBERS: [usize; 3] = [0, 64, 1024];
+
+/// Emit events to listeners that are all polled by the same task.
+fn emit_thread_unsafe(c: &mut Criterion) {
+    let mut group = c.benchmark_group("emit/thread_unsafe");
+
+    for listeners in LISTENERS {
+        group.bench_function(BenchmarkId::from_parameter(listeners), |b| {
+            b.iter_custom(|iters| {
+                let handler = Handler::<u64, ThreadUnsafe>::new();
+                let mut waiters = (0..listeners).map(|_| handler.wait()).collect::<Vec<_>>();
+
+                // Events are handed to the listeners in the order they were registered.
+                let receive = async {
+                    for _ in 0..iters {
+                        for waiter in &mut waiters {
+                            waiter.next().await.unwrap();
+                        }
+                    }
+                };
+                let emit = async {
+                    for i in 0..iters {
+                        handler.emit(i).await;
+                    }
+                };
+
+                let start = Instant::now();
+                future::block_on(future::zip(receive, emit));
+                start.elapsed()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Emit events to listeners that are each polled on their own thread.
+#[cfg(feature = "thread_safe")]
+fn emit_thread_safe(c: &mut Criterion) {
+    use async_winit::ThreadSafe;
+    use std::sync::Barrier;
+    use std::thread;
+
+    let mut group = c.benchmark_group("emit/thread_safe");
+
+    for listeners in LISTENERS {
+        group.bench_function(BenchmarkId::from_parameter(listeners), |b| {
+            b.iter_custom(|iters| {
+                let handler = Handler::<u64, ThreadSafe>::new();
+                let barrier = Barrier::new(listeners + 1);
+
+                thread::scope(|scope| {
+                    for _ in 0..listeners {
+                        scope.spawn(|| {
+                            let mut waiter = handler.wait();
+                            barrier.wait();
+
+                            future::block_on(async {
+                                for _ in 0..iters {
+                                    waiter.next().await.unwrap();
+                                }
+                            });
+                        });
+                    }
+
+                    // Wait for every listener to be registered.
+                    barrier.wait();
+
+                    let start = Instant::now();
+                    future::block_on(async {
+                        for i in 0..iters {
+                            handler.emit(i).await;
+                        }
+                    });
+                    start.elapsed()
+                })
+            });
+        });
+    }
+
+    group.finish();
+}
+
+#[cfg(not(feature = "thread_safe"))]
+fn emit_thread_safe(_c: &mut Criterion) {}
+
+/// Add and remove a listener on a handler that already has other listeners.
+fn subscribe_thread_unsafe(c: &mut Criterion) {
+    let mut group = c.benchmark_group("subscribe/thread_unsafe");
+
+    for listeners in SUBSCRIBERS {
+        group.bench_function(BenchmarkId::from_parameter(listeners), |b| {
+            let handler = Handler::<u64, ThreadUnsafe>::new();
+            let _waiters = (0..listeners).map(|_| handler.wait()).collect::<Vec<_>>();
+
+            b.iter(|| drop(black_box(handler.wait())));
+        });
+    }
+
+    group.finish();
+}
+
+/// Add and remove a listener on a handler that already has other listeners.
+#[cfg(feature = "thread_safe")]
+fn subscribe_thread_safe(c: &mut Criterion) {
+    use async_winit::ThreadSafe;
+
+    let mut group = c.benchmark_group("subscribe/thread_safe");
+
+    for listeners in SUBSCRIBERS {
+        group.bench_function(BenchmarkId::from_parameter(listeners), |b| {
+            let handler = Handler::<u64, ThreadSafe>::new();
+            let _waiters = (0..listeners).map(|_| handler.wait()).collect::<Vec<_>>();
+
+            b.iter(|| drop(black_box(handler.wait())));
+        });
+    }
+
+    group.finish();
+}
+
+#[cfg(not(feature = "thread_safe"))]
+fn subscribe_thread_safe(_c: &mut Criterion) {}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(3));
+    targets = emit_thread_unsafe, emit_thread_safe, subscribe_thread_unsafe, subscribe_thread_safe
+}
+criterion_main!(benches);
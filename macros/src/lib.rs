@@ -0,0 +1,143 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Procedural macros for [`async-winit`].
+//!
+//! This crate shouldn't be used directly. Enable the `macros` feature of `async-winit` and use the
+//! macros it re-exports instead.
+//!
+//! [`async-winit`]: https://crates.io/crates/async-winit
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, ItemFn, MetaNameValue, Token};
+
+/// The number of seconds a test can run for, if no timeout is given.
+const DEFAULT_TIMEOUT: u64 = 60;
+
+/// Run an `async` test inside of an event loop.
+///
+/// The test is run on an `async-winit` event loop that is created for it, and the reactor is
+/// destroyed once the test is done. Tests that use this attribute are run one at a time, since
+/// only one reactor can exist at a time. A test fails if a `Window`, `Timer` or task that uses
+/// the event loop outlives it, since that keeps the reactor alive, and later tests have to reuse
+/// it.
+///
+/// The test can take an `EventLoopWindowTarget` as its only argument, in which case the thread
+/// safety of the event loop is taken from its type. Otherwise, `DefaultThreadSafety` is used.
+/// The test can return anything that a normal test can, like a `Result`.
+///
+/// The test fails if it runs for longer than its timeout, which is 60 seconds by default. Use
+/// `timeout` to set it in seconds:
+///
+/// ```ignore
+/// use async_winit::event_loop::EventLoopWindowTarget;
+/// use async_winit::window::Window;
+/// use async_winit::ThreadUnsafe;
+///
+/// #[async_winit::test(timeout = 5)]
+/// async fn create_window(target: EventLoopWindowTarget<ThreadUnsafe>) {
+///     let window = Window::<ThreadUnsafe>::new().await.unwrap();
+///     window.set_title("Hello").await;
+///     target.set_exit();
+/// }
+/// ```
+///
+/// Without the `headless` feature, the event loop connects to the display and is allowed to run
+/// on the test's thread. `winit` only allows one event loop to be created per process, so only
+/// one test per test binary can do this.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let item = syn::parse_macro_input!(item as ItemFn);
+
+    expand_test(args, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_test(
+    args: Punctuated<MetaNameValue, Token![,]>,
+    item: ItemFn,
+) -> syn::Result<TokenStream2> {
+    let mut timeout: Expr = syn::parse_quote!(#DEFAULT_TIMEOUT);
+    for arg in args {
+        if arg.path.is_ident("timeout") {
+            timeout = arg.value;
+        } else {
+            return Err(syn::Error::new(arg.path.span(), "unknown argument"));
+        }
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    if sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            sig.fn_token.span(),
+            "the `async` keyword is missing from the function declaration",
+        ));
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "tests can't have generic parameters",
+        ));
+    }
+    if sig.inputs.len() > 1 {
+        return Err(syn::Error::new(
+            sig.inputs.span(),
+            "tests can only take an `EventLoopWindowTarget`",
+        ));
+    }
+
+    let name = &sig.ident;
+    let output = &sig.output;
+    let test = if sig.inputs.is_empty() {
+        quote! {
+            |_: ::async_winit::event_loop::EventLoopWindowTarget| #name()
+        }
+    } else {
+        quote! { #name }
+    };
+
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            #sig #block
+
+            ::async_winit::__run_test(
+                ::core::stringify!(#name),
+                ::core::time::Duration::from_secs(#timeout),
+                #test,
+            )
+        }
+    })
+}
@@ -0,0 +1,717 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Driving the application from other processes.
+//!
+//! An [`Automation`] server listens on a Unix domain socket and lets external scripts, such as
+//! end-to-end tests or accessibility tools, inspect the application's windows and send input to
+//! them. It is started with [`EventLoopWindowTarget::start_automation`], and requests are answered
+//! while the future returned by [`Automation::serve`] is running.
+//!
+//! # Protocol
+//!
+//! Clients send one JSON object per line, and the server answers each request with one line, in
+//! the order that the requests were sent. A request has a `method`, its `params` and an optional
+//! `id` that is copied into the response:
+//!
+//! ```json
+//! {"id": 1, "method": "window_state", "params": {"window": 4194305}}
+//! ```
+//!
+//! A response has either a `result` or an `error` message:
+//!
+//! ```json
+//! {"id": 1, "result": {"id": 4194305, "title": "Hello", "focused": true, ...}}
+//! {"id": 1, "error": "no window with ID 4194305"}
+//! ```
+//!
+//! The methods are:
+//!
+//! - `list_windows` returns the ID of every window and whether it has focus, as a list of
+//!   `{"id", "focused"}` objects.
+//! - `window_state` takes a `window` ID and returns the window's title, size, position,
+//!   visibility and other state.
+//! - `inject` takes a `window` ID and an input `event`, and returns `null` once the event has been
+//!   delivered to the window's handlers.
+//! - `wait_for` takes the name of a `condition` registered with [`Automation::add_condition`] and
+//!   an optional `timeout_ms`, which defaults to ten seconds. It returns `null` once the condition
+//!   holds, or an error if the timeout is reached first. The condition is checked and the timeout
+//!   is measured on the system clock, even if the event loop's timers use another
+//!   [`Clock`](crate::Clock).
+//!
+//! # Input events
+//!
+//! Input events are objects with a `type`. Enumerations like `state` and `button` use the names of
+//! the `winit` types, such as `"Pressed"`, `"Left"` or `{"Other": 8}`.
+//!
+//! - `{"type": "cursor_moved", "x": 10.0, "y": 20.0}`
+//! - `{"type": "cursor_entered"}` and `{"type": "cursor_left"}`
+//! - `{"type": "mouse_input", "state": "Pressed", "button": "Left"}`
+//! - `{"type": "mouse_wheel", "delta": {"LineDelta": [0.0, -1.0]}}`, with an optional `phase`
+//!   that defaults to `"Moved"`
+//! - `{"type": "touch", "id": 0, "phase": "Started", "x": 10.0, "y": 20.0}`
+//! - `{"type": "text", "text": "hello"}`
+//! - `{"type": "modifiers", "shift": true}`, with optional `control`, `alt` and `super` flags
+//!
+//! `winit` doesn't allow creating keyboard input events, so text is delivered as an
+//! [`Ime::Commit`] event instead.
+//!
+//! # Security
+//!
+//! Anyone who can connect to the socket can control the application. The socket is only readable
+//! and writable by the current user, but it should still be created in a directory that only the
+//! current user can access, since other users may be able to connect before its permissions are
+//! set. The server is never started unless [`EventLoopWindowTarget::start_automation`] is called.
+//!
+//! This module is only available on Unix with the `automation` feature.
+
+use crate::event::Ime;
+use crate::event_loop::EventLoopWindowTarget;
+use crate::keyboard::ModifiersState;
+use crate::reactor::Reactor;
+use crate::sync::__private::{__ThreadSafety, Mutex as _, Rc as _};
+use crate::sync::ThreadSafety;
+use crate::window::Window;
+use crate::DefaultThreadSafety;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::future::{self, Future};
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use slab::Slab;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceId, ElementState, MouseButton, MouseScrollDelta, Touch, TouchPhase, WindowEvent,
+};
+use winit::window::{Theme, WindowId};
+
+/// How long `wait_for` waits for a condition if the request doesn't say.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often conditions are checked while waiting for them.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A condition that clients can wait for.
+type ConditionFn = Box<dyn FnMut() -> bool + Send + 'static>;
+
+/// A registered condition.
+///
+/// This is reference counted so it can be checked without holding the list locked.
+type Condition<TS> = <TS as __ThreadSafety>::Rc<<TS as __ThreadSafety>::Mutex<ConditionFn>>;
+
+/// A request from a client.
+#[derive(Deserialize)]
+struct Request {
+    /// The ID to copy into the response.
+    #[serde(default)]
+    id: Value,
+
+    /// What the client is asking for.
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// The methods that clients can call.
+#[derive(Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Command {
+    ListWindows,
+    WindowState {
+        window: u64,
+    },
+    Inject {
+        window: u64,
+        event: InputEvent,
+    },
+    WaitFor {
+        condition: String,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+    },
+}
+
+/// An input event to inject into a window.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum InputEvent {
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    CursorEntered,
+    CursorLeft,
+    MouseInput {
+        state: ElementState,
+        button: MouseButton,
+    },
+    MouseWheel {
+        delta: MouseScrollDelta,
+        #[serde(default = "default_phase")]
+        phase: TouchPhase,
+    },
+    Touch {
+        #[serde(default)]
+        id: u64,
+        phase: TouchPhase,
+        x: f64,
+        y: f64,
+    },
+    Text {
+        text: String,
+    },
+    Modifiers {
+        #[serde(default)]
+        shift: bool,
+        #[serde(default)]
+        control: bool,
+        #[serde(default)]
+        alt: bool,
+        #[serde(default, rename = "super")]
+        super_key: bool,
+    },
+}
+
+fn default_phase() -> TouchPhase {
+    TouchPhase::Moved
+}
+
+impl InputEvent {
+    /// Convert this event into a window event.
+    fn into_window_event(self) -> WindowEvent {
+        // SAFETY: The dummy ID is only compared against other device IDs.
+        let device_id = unsafe { DeviceId::dummy() };
+
+        match self {
+            Self::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(x, y),
+            },
+            Self::CursorEntered => WindowEvent::CursorEntered { device_id },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseInput { state, button } => WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+            },
+            Self::MouseWheel { delta, phase } => WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase,
+            },
+            Self::Touch { id, phase, x, y } => WindowEvent::Touch(Touch {
+                device_id,
+                phase,
+                location: PhysicalPosition::new(x, y),
+                force: None,
+                id,
+            }),
+            Self::Text { text } => WindowEvent::Ime(Ime::Commit(text)),
+            Self::Modifiers {
+                shift,
+                control,
+                alt,
+                super_key,
+            } => {
+                let mut state = ModifiersState::empty();
+                state.set(ModifiersState::SHIFT, shift);
+                state.set(ModifiersState::CONTROL, control);
+                state.set(ModifiersState::ALT, alt);
+                state.set(ModifiersState::SUPER, super_key);
+                WindowEvent::ModifiersChanged(state.into())
+            }
+        }
+    }
+}
+
+/// A response to a request.
+#[derive(Serialize)]
+struct Response {
+    /// The ID of the request.
+    id: Value,
+
+    /// The result of the request.
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+/// The result of a request.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Result(Value),
+    Error(String),
+}
+
+/// A window, as returned by `list_windows`.
+#[derive(Serialize)]
+struct WindowSummary {
+    id: u64,
+    focused: bool,
+}
+
+/// The state of a window, as returned by `window_state`.
+#[derive(Serialize)]
+struct WindowState {
+    id: u64,
+    title: String,
+    focused: bool,
+    visible: Option<bool>,
+    minimized: Option<bool>,
+    maximized: bool,
+    fullscreen: bool,
+    resizable: bool,
+    decorated: bool,
+    theme: Option<Theme>,
+    scale_factor: f64,
+    inner_size: PhysicalSize<u32>,
+    outer_size: PhysicalSize<u32>,
+    inner_position: Option<PhysicalPosition<i32>>,
+    outer_position: Option<PhysicalPosition<i32>>,
+}
+
+impl WindowState {
+    /// Query the state of a window.
+    async fn query<TS: ThreadSafety>(window: &Window<TS>) -> Self {
+        Self {
+            id: window.id().into(),
+            title: window.title().await,
+            focused: window.is_focused().await,
+            visible: window.is_visible().await,
+            minimized: window.is_minimized().await,
+            maximized: window.is_maximized().await,
+            fullscreen: window.fullscreen().await.is_some(),
+            resizable: window.is_resizable().await,
+            decorated: window.is_decorated().await,
+            theme: window.theme().await,
+            scale_factor: window.scale_factor(),
+            inner_size: window.inner_size().await,
+            outer_size: window.outer_size().await,
+            inner_position: window.inner_position().await.ok(),
+            outer_position: window.outer_position().await.ok(),
+        }
+    }
+}
+
+/// A request waiting to be answered by [`Automation::serve`].
+struct Pending {
+    /// The ID of the request.
+    id: Value,
+
+    /// What the client is asking for.
+    command: Command,
+
+    /// Sends the response back to the connection's thread.
+    reply: mpsc::Sender<Response>,
+}
+
+/// State shared between the server and the threads handling the socket.
+struct Shared {
+    /// Requests waiting to be answered.
+    requests: Mutex<VecDeque<Pending>>,
+
+    /// Wakes up `Automation::serve` once a request comes in.
+    waker: Mutex<Option<Waker>>,
+
+    /// The open connections, so they can be closed once the server stops.
+    connections: Mutex<Slab<UnixStream>>,
+
+    /// Whether the server has stopped.
+    closed: AtomicBool,
+}
+
+impl Shared {
+    /// Accept connections until the server stops.
+    fn accept(self: Arc<Self>, listener: UnixListener) {
+        for stream in listener.incoming() {
+            if self.closed.load(Ordering::Acquire) {
+                break;
+            }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            let key = match stream.try_clone() {
+                Ok(clone) => self.connections.lock().unwrap().insert(clone),
+                Err(_) => continue,
+            };
+
+            let shared = self.clone();
+            let spawned = thread::Builder::new()
+                .name("async-winit-automation".into())
+                .spawn(move || {
+                    shared.handle_connection(&stream).ok();
+                    shared.connections.lock().unwrap().remove(key);
+                });
+
+            if spawned.is_err() {
+                self.connections.lock().unwrap().remove(key);
+            }
+        }
+    }
+
+    /// Answer the requests on a connection until it is closed.
+    fn handle_connection(&self, stream: &UnixStream) -> io::Result<()> {
+        let mut writer = stream;
+
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(value) => {
+                    let id = value.get("id").cloned().unwrap_or_default();
+                    match Request::deserialize(value) {
+                        Ok(request) => match self.submit(request) {
+                            Some(response) => response,
+                            None => break,
+                        },
+                        Err(err) => Response {
+                            id,
+                            outcome: Outcome::Error(format!("invalid request: {err}")),
+                        },
+                    }
+                }
+                Err(err) => Response {
+                    id: Value::Null,
+                    outcome: Outcome::Error(format!("invalid JSON: {err}")),
+                },
+            };
+
+            serde_json::to_writer(&mut writer, &response)?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Hand a request to the server and wait for its response.
+    ///
+    /// Returns `None` if the server stopped before answering.
+    fn submit(&self, request: Request) -> Option<Response> {
+        let (reply, response) = mpsc::channel();
+
+        {
+            let mut requests = self.requests.lock().unwrap();
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+
+            requests.push_back(Pending {
+                id: request.id,
+                command: request.command,
+                reply,
+            });
+        }
+
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+
+        response.recv().ok()
+    }
+}
+
+/// Wakes up a task periodically on the system clock.
+struct Ticker {
+    /// The waker to wake up on the next tick.
+    waker: Arc<Mutex<Option<Waker>>>,
+
+    /// Stops the ticking thread once dropped.
+    _stop: mpsc::Sender<()>,
+}
+
+impl Ticker {
+    fn start(interval: Duration) -> Self {
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let (stop, stopped) = mpsc::channel();
+
+        thread::spawn({
+            let waker = waker.clone();
+            move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    if let Some(waker) = waker.lock().unwrap().take() {
+                        waker.wake();
+                    }
+                }
+            }
+        });
+
+        Self { waker, _stop: stop }
+    }
+
+    /// Wake up `waker` on the next tick.
+    fn register(&self, waker: &Waker) {
+        *self.waker.lock().unwrap() = Some(waker.clone());
+    }
+}
+
+impl<TS: ThreadSafety> EventLoopWindowTarget<TS> {
+    /// Start an automation server on a Unix domain socket at `path`.
+    ///
+    /// Requests are answered while [`Automation::serve`] is running. The server stops and the
+    /// socket is removed once the returned [`Automation`] is dropped. This fails if a file already
+    /// exists at `path`.
+    pub fn start_automation(&self, path: impl AsRef<Path>) -> io::Result<Automation<TS>> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        if let Err(err) = fs::set_permissions(&path, fs::Permissions::from_mode(0o600)) {
+            fs::remove_file(&path).ok();
+            return Err(err);
+        }
+
+        let shared = Arc::new(Shared {
+            requests: Mutex::new(VecDeque::new()),
+            waker: Mutex::new(None),
+            connections: Mutex::new(Slab::new()),
+            closed: AtomicBool::new(false),
+        });
+
+        let spawned = thread::Builder::new()
+            .name("async-winit-automation".into())
+            .spawn({
+                let shared = shared.clone();
+                move || shared.accept(listener)
+            });
+
+        if let Err(err) = spawned {
+            fs::remove_file(&path).ok();
+            return Err(err);
+        }
+
+        Ok(Automation {
+            path,
+            shared,
+            conditions: TS::Mutex::new(HashMap::new()),
+            reactor: Reactor::<TS>::get(),
+        })
+    }
+}
+
+/// A running automation server.
+///
+/// This is returned by [`EventLoopWindowTarget::start_automation`]. The server stops once this is
+/// dropped.
+pub struct Automation<TS: ThreadSafety = DefaultThreadSafety> {
+    /// The path to the socket.
+    path: PathBuf,
+
+    /// State shared with the threads handling the socket.
+    shared: Arc<Shared>,
+
+    /// The conditions that clients can wait for.
+    conditions: TS::Mutex<HashMap<String, Condition<TS>>>,
+
+    /// The reactor to look up windows in.
+    reactor: TS::Rc<Reactor<TS>>,
+}
+
+impl<TS: ThreadSafety> fmt::Debug for Automation<TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Automation")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<TS: ThreadSafety> Automation<TS> {
+    /// Get the path to the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Register a condition that clients can wait for with `wait_for`.
+    ///
+    /// While a client is waiting, the condition is checked every 10 milliseconds until it returns
+    /// `true`. A condition with the same name as an existing one replaces it.
+    pub fn add_condition(
+        &self,
+        name: impl Into<String>,
+        condition: impl FnMut() -> bool + Send + 'static,
+    ) {
+        let condition: ConditionFn = Box::new(condition);
+        self.conditions
+            .lock()
+            .unwrap()
+            .insert(name.into(), TS::Rc::new(TS::Mutex::new(condition)));
+    }
+
+    /// Remove a condition.
+    ///
+    /// Returns `false` if there was no condition with this name. Clients that are already waiting
+    /// for the condition keep waiting for it.
+    pub fn remove_condition(&self, name: &str) -> bool {
+        self.conditions.lock().unwrap().remove(name).is_some()
+    }
+
+    /// Answer requests from clients.
+    ///
+    /// This never completes, and should be run alongside the rest of the application. Requests
+    /// are answered concurrently, so a client waiting for a condition doesn't hold up the others.
+    pub async fn serve(&self) {
+        let mut tasks: Vec<Pin<Box<dyn Future<Output = ()> + '_>>> = Vec::new();
+
+        future::poll_fn(|cx| {
+            // Store the waker before taking the requests, so we don't miss any.
+            *self.shared.waker.lock().unwrap() = Some(cx.waker().clone());
+
+            let requests = mem::take(&mut *self.shared.requests.lock().unwrap());
+            for pending in requests {
+                tasks.push(Box::pin(self.answer(pending)));
+            }
+
+            tasks.retain_mut(|task| task.as_mut().poll(cx).is_pending());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Answer a request.
+    async fn answer(&self, pending: Pending) {
+        let outcome = match self.handle(pending.command).await {
+            Ok(result) => Outcome::Result(result),
+            Err(err) => Outcome::Error(err),
+        };
+
+        pending
+            .reply
+            .send(Response {
+                id: pending.id,
+                outcome,
+            })
+            .ok();
+    }
+
+    /// Run a command.
+    async fn handle(&self, command: Command) -> Result<Value, String> {
+        match command {
+            Command::ListWindows => {
+                let focused = self.reactor.focused_window();
+                let mut windows = self
+                    .reactor
+                    .windows()
+                    .into_iter()
+                    .map(|(id, _)| WindowSummary {
+                        id: id.into(),
+                        focused: focused == Some(id),
+                    })
+                    .collect::<Vec<_>>();
+                windows.sort_by_key(|window| window.id);
+
+                to_value(windows)
+            }
+            Command::WindowState { window } => {
+                let window = self.window(window)?;
+                to_value(WindowState::query(&window).await)
+            }
+            Command::Inject { window, event } => {
+                let window = self.window(window)?;
+                window.inject(event.into_window_event()).await;
+                Ok(Value::Null)
+            }
+            Command::WaitFor {
+                condition: name,
+                timeout_ms,
+            } => {
+                let condition = self
+                    .conditions
+                    .lock()
+                    .unwrap()
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| format!("no condition named `{name}`"))?;
+
+                // Use the system clock, since the reactor's clock may be a `ManualClock`.
+                let timeout = timeout_ms.map_or(DEFAULT_TIMEOUT, Duration::from_millis);
+                let deadline = Instant::now().checked_add(timeout);
+                let ticker = Ticker::start(POLL_INTERVAL);
+
+                future::poll_fn(|cx| {
+                    ticker.register(cx.waker());
+
+                    if (condition.lock().unwrap())() {
+                        Poll::Ready(Ok(Value::Null))
+                    } else if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                        Poll::Ready(Err(format!("timed out waiting for `{name}`")))
+                    } else {
+                        Poll::Pending
+                    }
+                })
+                .await
+            }
+        }
+    }
+
+    /// Look up a window by its ID.
+    fn window(&self, id: u64) -> Result<Window<TS>, String> {
+        let id = WindowId::from(id);
+        self.reactor
+            .windows()
+            .into_iter()
+            .find(|(window, _)| *window == id)
+            .and_then(|(_, registration)| {
+                Window::from_registration(self.reactor.clone(), registration)
+            })
+            .ok_or_else(|| format!("no window with ID {}", u64::from(id)))
+    }
+}
+
+impl<TS: ThreadSafety> Drop for Automation<TS> {
+    fn drop(&mut self) {
+        {
+            let mut requests = self.shared.requests.lock().unwrap();
+            self.shared.closed.store(true, Ordering::Release);
+
+            // Dropping the requests tells their connections that they won't be answered.
+            requests.clear();
+        }
+
+        // Wake up the accepting thread so it notices that the server has stopped.
+        UnixStream::connect(&self.path).ok();
+
+        for (_, stream) in self.shared.connections.lock().unwrap().iter() {
+            stream.shutdown(Shutdown::Both).ok();
+        }
+
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Convert a result into JSON.
+fn to_value(value: impl Serialize) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|err| err.to_string())
+}
@@ -0,0 +1,160 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Clocks used to drive timers.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+/// A source of the current time for the reactor.
+///
+/// All [`Timer`]s and the [`ControlFlow::WaitUntil`] deadlines of the event loop are computed from
+/// the reactor's clock. By default this is the [`SystemClock`]; a different clock can be installed
+/// with [`EventLoopBuilder::with_clock`].
+///
+/// [`Timer`]: crate::Timer
+/// [`ControlFlow::WaitUntil`]: crate::event_loop::ControlFlow::WaitUntil
+/// [`EventLoopBuilder::with_clock`]: crate::event_loop::EventLoopBuilder::with_clock
+pub trait Clock: Send + Sync + 'static {
+    /// Get the current time.
+    fn now(&self) -> Instant;
+
+    /// Register a waker to wake up when the time jumps.
+    ///
+    /// Clocks that do not follow the system clock should wake up this waker when their time
+    /// changes, so that the event loop can fire the timers that are now due. Only the most recently
+    /// registered waker needs to be woken. The default implementation does nothing.
+    fn register(&self, waker: &Waker) {
+        let _ = waker;
+    }
+}
+
+/// The clock that follows the system's monotonic time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock whose time only moves when it is advanced explicitly.
+///
+/// This is useful for testing code that uses timers, as a `Timer::after(Duration::from_secs(5))`
+/// can be fired instantly by advancing the clock by five seconds. Timers still fire in the order
+/// of their deadlines.
+///
+/// Clones of a `ManualClock` share the same time.
+#[derive(Clone)]
+pub struct ManualClock {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// The current time.
+    now: Mutex<Instant>,
+
+    /// The waker to wake up when the time changes.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Create a new clock, starting at the current system time.
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// Create a new clock, starting at the given time.
+    pub fn starting_at(start: Instant) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                now: Mutex::new(start),
+                waker: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Get the current time of this clock.
+    pub fn now(&self) -> Instant {
+        *self.inner.now.lock().unwrap()
+    }
+
+    /// Move this clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        {
+            let mut now = self.inner.now.lock().unwrap();
+            *now = now
+                .checked_add(duration)
+                .expect("overflow when advancing the clock");
+        }
+
+        self.wake();
+    }
+
+    /// Move this clock forward to the given time.
+    ///
+    /// Does nothing if the clock is already past `time`.
+    pub fn advance_to(&self, time: Instant) {
+        {
+            let mut now = self.inner.now.lock().unwrap();
+            if time <= *now {
+                return;
+            }
+
+            *now = time;
+        }
+
+        self.wake();
+    }
+
+    fn wake(&self) {
+        let waker = self.inner.waker.lock().unwrap().clone();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        ManualClock::now(self)
+    }
+
+    fn register(&self, waker: &Waker) {
+        *self.inner.waker.lock().unwrap() = Some(waker.clone());
+    }
+}
@@ -24,7 +24,8 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 //!   and run it to completion. Eent handling is done through the [`Handler`] structures instead.
 //! - Methods on [`EventLoop`] and [`EventLoopWindowTarget`] are `async`.
 //! - There is no `EventLoopProxy` type, since it is now obsolete with `async` blocks. Instead,
-//!   consider using an async channel to communicate with the event loop.
+//!   consider using an async channel to communicate with the event loop, or a [`UserEventSender`]
+//!   to deliver typed events to [`EventLoopWindowTarget::user_events`] handlers.
 //!
 //! ```no_run
 //! use async_winit::event_loop::EventLoop;
@@ -48,6 +49,7 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 //!
 //! [`Handler`]: crate::Handler
 
+use crate::clock::Clock;
 use crate::handler::Handler;
 use crate::reactor::{EventLoopOp, Reactor};
 use crate::sync::ThreadSafety;
@@ -56,14 +58,20 @@ use crate::DefaultThreadSafety;
 use std::convert::Infallible;
 use std::fmt;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::ops;
+use std::sync::Arc;
 
 use raw_window_handle::{HasRawDisplayHandle, RawDisplayHandle};
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
 use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
 
 #[doc(inline)]
 pub use winit::event_loop::{ControlFlow, EventLoopClosed};
 
+pub use crate::oneoff::LoopClosed;
+
 /// Used to indicate that we need to wake up the event loop.
 ///
 /// This is a ZST used by the underlying event loop to wake up the event loop. It is not used
@@ -77,6 +85,26 @@ pub struct Wakeup {
     pub(crate) _private: (),
 }
 
+/// A user event type that can carry the [`Wakeup`] event.
+///
+/// This lets a [`Filter`] be used with an existing `winit` event loop that has its own user event
+/// type. The filter sends wakeups through the event loop by converting them with `From<Wakeup>`,
+/// and uses [`is_wakeup`](WakeupEvent::is_wakeup) to tell them apart from the application's own
+/// events.
+///
+/// [`Filter`]: crate::filter::Filter
+pub trait WakeupEvent: From<Wakeup> + Send + 'static {
+    /// Tell whether this event is a wakeup sent by `async-winit`.
+    fn is_wakeup(&self) -> bool;
+}
+
+impl WakeupEvent for Wakeup {
+    #[inline]
+    fn is_wakeup(&self) -> bool {
+        true
+    }
+}
+
 /// Provides a way to retrieve events from the system and from the windows that were registered to
 /// the events loop.
 ///
@@ -92,8 +120,8 @@ pub struct Wakeup {
 ///
 /// [`Window`]: crate::window::Window
 pub struct EventLoop<TS: ThreadSafety = DefaultThreadSafety> {
-    /// The underlying event loop.
-    pub(crate) inner: winit::event_loop::EventLoop<Wakeup>,
+    /// The underlying event loop, or `None` if the event loop is headless.
+    pub(crate) inner: Option<winit::event_loop::EventLoop<Wakeup>>,
 
     /// The window target.
     window_target: EventLoopWindowTarget<TS>,
@@ -115,11 +143,11 @@ pub struct EventLoopWindowTarget<TS: ThreadSafety = DefaultThreadSafety> {
     /// The associated reactor, cached for convenience.
     reactor: TS::Rc<Reactor<TS>>,
 
-    /// The event loop proxy.
-    proxy: EventLoopProxy<Wakeup>,
+    /// The event loop proxy, or `None` if the event loop is headless.
+    proxy: Option<EventLoopProxy<Wakeup>>,
 
-    /// The raw display handle.
-    raw_display_handle: RawDisplayHandle,
+    /// The raw display handle, or `None` if the event loop is headless.
+    raw_display_handle: Option<RawDisplayHandle>,
 
     /// Is this using wayland?
     #[cfg(any(x11_platform, wayland_platform))]
@@ -144,6 +172,96 @@ impl<TS: ThreadSafety> Clone for EventLoopWindowTarget<TS> {
     }
 }
 
+/// What to do with a window event after it has been intercepted.
+///
+/// This is returned by interceptors added with [`EventLoopWindowTarget::add_interceptor`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterceptAction {
+    /// Pass the event on to the next interceptor, or to the window's handlers.
+    Continue,
+
+    /// Drop the event.
+    Drop,
+
+    /// Replace the event with another one.
+    Replace(WindowEvent),
+}
+
+/// A lifecycle event that can be injected with [`EventLoopWindowTarget::inject_lifecycle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LifecycleEvent {
+    /// The application was resumed.
+    Resumed,
+
+    /// The application was suspended.
+    Suspended,
+}
+
+/// An interceptor added with [`EventLoopWindowTarget::add_interceptor`].
+///
+/// The interceptor is removed once this is dropped.
+#[must_use = "the interceptor is removed once this is dropped"]
+pub struct Interceptor<TS: ThreadSafety = DefaultThreadSafety> {
+    /// The associated reactor.
+    reactor: TS::Rc<Reactor<TS>>,
+
+    /// The ID of the interceptor.
+    id: usize,
+}
+
+impl<TS: ThreadSafety> fmt::Debug for Interceptor<TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interceptor")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<TS: ThreadSafety> Drop for Interceptor<TS> {
+    fn drop(&mut self) {
+        self.reactor.remove_interceptor(self.id);
+    }
+}
+
+/// Sends user events of type `T` to the event loop.
+///
+/// The events are delivered to the handler returned by [`EventLoopWindowTarget::user_events`].
+/// This type is cheaply clonable, and when using thread-safe primitives it can be sent to other
+/// threads.
+pub struct UserEventSender<T: Clone + 'static, TS: ThreadSafety = DefaultThreadSafety> {
+    /// The associated reactor.
+    reactor: TS::Rc<Reactor<TS>>,
+
+    /// The type of events that are sent.
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T: Clone + 'static, TS: ThreadSafety> fmt::Debug for UserEventSender<T, TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UserEventSender { .. }")
+    }
+}
+
+impl<T: Clone + 'static, TS: ThreadSafety> Clone for UserEventSender<T, TS> {
+    fn clone(&self) -> Self {
+        Self {
+            reactor: self.reactor.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static, TS: ThreadSafety> UserEventSender<T, TS> {
+    /// Send an event to the event loop.
+    ///
+    /// This wakes up the event loop if it is asleep. The event is stamped with the time at which
+    /// it was sent.
+    #[inline]
+    pub fn send(&self, event: T) {
+        self.reactor.send_user_event(event);
+    }
+}
+
 /// Object that allows for building the [`EventLoop`].
 ///
 /// This specifies options that affect the whole application, like the current Android app or whether
@@ -151,6 +269,9 @@ impl<TS: ThreadSafety> Clone for EventLoopWindowTarget<TS> {
 pub struct EventLoopBuilder {
     /// The underlying builder.
     pub(crate) inner: winit::event_loop::EventLoopBuilder<Wakeup>,
+
+    /// The clock to use for timers.
+    clock: Option<Arc<dyn Clock>>,
 }
 
 impl fmt::Debug for EventLoopBuilder {
@@ -164,9 +285,21 @@ impl EventLoopBuilder {
     pub fn new() -> Self {
         Self {
             inner: winit::event_loop::EventLoopBuilder::with_user_event(),
+            clock: None,
         }
     }
 
+    /// Use the given clock for timers instead of the system clock.
+    ///
+    /// This is mostly useful for tests, where a [`ManualClock`] lets timers fire without waiting
+    /// for them in real time.
+    ///
+    /// [`ManualClock`]: crate::ManualClock
+    pub fn with_clock(&mut self, clock: impl Clock) -> &mut Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
     /// Builds a new event loop.
     ///
     /// In general, this function must be called on the same thread that `main()` is being run inside of.
@@ -179,11 +312,17 @@ impl EventLoopBuilder {
     /// [`platform`]: crate::platform
     pub fn build<TS: ThreadSafety>(&mut self) -> EventLoop<TS> {
         let inner = self.inner.build().unwrap();
+
+        let reactor = Reactor::<TS>::get();
+        if let Some(clock) = self.clock.take() {
+            reactor.set_clock(clock);
+        }
+
         EventLoop {
             window_target: EventLoopWindowTarget {
-                reactor: Reactor::<TS>::get(),
-                proxy: inner.create_proxy(),
-                raw_display_handle: inner.raw_display_handle(),
+                reactor,
+                proxy: Some(inner.create_proxy()),
+                raw_display_handle: Some(inner.raw_display_handle()),
                 #[cfg(any(x11_platform, wayland_platform))]
                 is_wayland: {
                     cfg_if::cfg_if! {
@@ -199,7 +338,32 @@ impl EventLoopBuilder {
                     }
                 },
             },
-            inner,
+            inner: Some(inner),
+        }
+    }
+
+    /// Builds a new event loop that doesn't connect to a display.
+    ///
+    /// Windows created while this event loop is running only exist in memory. See the
+    /// [`headless`] module for more information.
+    ///
+    /// [`headless`]: crate::headless
+    #[cfg(feature = "headless")]
+    pub fn build_headless<TS: ThreadSafety>(&mut self) -> EventLoop<TS> {
+        let reactor = Reactor::<TS>::get();
+        if let Some(clock) = self.clock.take() {
+            reactor.set_clock(clock);
+        }
+
+        EventLoop {
+            window_target: EventLoopWindowTarget {
+                reactor,
+                proxy: None,
+                raw_display_handle: None,
+                #[cfg(any(x11_platform, wayland_platform))]
+                is_wayland: false,
+            },
+            inner: None,
         }
     }
 }
@@ -212,7 +376,7 @@ impl Default for EventLoopBuilder {
 
 unsafe impl<TS: ThreadSafety> HasRawDisplayHandle for EventLoop<TS> {
     fn raw_display_handle(&self) -> RawDisplayHandle {
-        self.window_target.raw_display_handle
+        self.window_target.raw_display_handle()
     }
 }
 
@@ -272,6 +436,98 @@ impl<TS: ThreadSafety> EventLoopWindowTarget<TS> {
         &self.reactor.evl_registration.suspended
     }
 
+    /// Get the handler for events from devices.
+    ///
+    /// This receives raw input that isn't associated with a window, such as mouse motion and key
+    /// presses, along with the ID of the device that it came from.
+    #[inline]
+    pub fn device_event(&self) -> &Handler<(DeviceId, DeviceEvent), TS> {
+        &self.reactor.evl_registration.device_event
+    }
+
+    /// Get the handler for user events of type `T`.
+    ///
+    /// Events sent through a [`UserEventSender<T>`] are delivered to this handler by the event
+    /// loop, in the order that they were sent.
+    #[inline]
+    pub fn user_events<T: Clone + Send + 'static>(&self) -> &Handler<T, TS> {
+        &self.reactor.user_event_slot::<T>().handler
+    }
+
+    /// Get the handler for the `CloseRequested` event of any window.
+    ///
+    /// This receives the ID of the window that the event is for, after the window's own handler
+    /// has processed the event.
+    #[inline]
+    pub fn any_close_requested(&self) -> &Handler<WindowId, TS> {
+        &self.reactor.evl_registration.any_close_requested
+    }
+
+    /// Get the handler for the `Focused` event of any window.
+    ///
+    /// This receives the ID of the window that the event is for, after the window's own handler
+    /// has processed the event.
+    #[inline]
+    pub fn any_focused(&self) -> &Handler<(WindowId, bool), TS> {
+        &self.reactor.evl_registration.any_focused
+    }
+
+    /// Get the handler for the `KeyboardInput` event of any window.
+    ///
+    /// This receives the ID of the window that the event is for, after the window's own handler
+    /// has processed the event.
+    #[inline]
+    pub fn any_keyboard_input(&self) -> &Handler<(WindowId, crate::event::KeyboardInput), TS> {
+        &self.reactor.evl_registration.any_keyboard_input
+    }
+
+    /// Get the window that currently has focus, if any.
+    #[inline]
+    pub fn focused_window(&self) -> Option<WindowId> {
+        self.reactor.focused_window()
+    }
+
+    /// Add an interceptor that sees every window event before the window's handlers do.
+    ///
+    /// Interceptors run in order of descending `priority`, and interceptors with the same priority
+    /// run in the order they were added. Each one can let the event through, drop it, or replace
+    /// it with another event that is then passed to the next interceptor.
+    ///
+    /// The interceptor is removed once the returned [`Interceptor`] is dropped.
+    pub fn add_interceptor(
+        &self,
+        priority: i32,
+        f: impl FnMut(&WindowId, &mut WindowEvent) -> InterceptAction + Send + 'static,
+    ) -> Interceptor<TS> {
+        let id = self.reactor.insert_interceptor(priority, Box::new(f));
+        Interceptor {
+            reactor: self.reactor.clone(),
+            id,
+        }
+    }
+
+    /// Create a sender for user events of type `T`.
+    #[inline]
+    pub fn user_event_sender<T: Clone + Send + 'static>(&self) -> UserEventSender<T, TS> {
+        UserEventSender {
+            reactor: self.reactor.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Inject a synthetic lifecycle event, as if it came from the event loop.
+    ///
+    /// The event is delivered to the [`resumed`](Self::resumed) or [`suspended`](Self::suspended)
+    /// handler, and this waits until it has been dispatched. This can be called from any thread.
+    pub async fn inject_lifecycle(&self, event: LifecycleEvent) {
+        let event = match event {
+            LifecycleEvent::Resumed => winit::event::Event::Resumed,
+            LifecycleEvent::Suspended => winit::event::Event::Suspended,
+        };
+
+        self.reactor.inject(event).await;
+    }
+
     /// Get the primary monitor.
     #[inline]
     pub async fn primary_monitor(&self) -> Option<winit::monitor::MonitorHandle> {
@@ -294,8 +550,14 @@ impl<TS: ThreadSafety> EventLoopWindowTarget<TS> {
 }
 
 unsafe impl<TS: ThreadSafety> HasRawDisplayHandle for EventLoopWindowTarget<TS> {
+    /// Get the raw display handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event loop is headless, since it isn't connected to a display.
     fn raw_display_handle(&self) -> RawDisplayHandle {
         self.raw_display_handle
+            .expect("headless event loops don't have a display")
     }
 }
 
@@ -307,12 +569,23 @@ impl<TS: ThreadSafety + 'static> EventLoop<TS> {
     }
 
     /// Block on a future forever.
+    ///
+    /// A headless event loop returns once it is asked to exit.
     #[inline]
     pub fn block_on(
         self,
         future: impl Future<Output = Infallible> + 'static,
     ) -> Result<(), winit::error::EventLoopError> {
-        let inner = self.inner;
+        let inner = match self.inner {
+            Some(inner) => inner,
+            #[cfg(feature = "headless")]
+            None => {
+                crate::headless::block_on(&self.window_target.reactor, future);
+                return Ok(());
+            }
+            #[cfg(not(feature = "headless"))]
+            None => unreachable!("headless event loops need the `headless` feature"),
+        };
 
         let mut future = Box::pin(future);
         let mut filter = crate::filter::Filter::<TS>::new(&inner);
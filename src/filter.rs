@@ -21,10 +21,15 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 //! This module is exposed such that it is possible to integrate `async-winit` easily with existing
 //! `winit` applications. The `Filter` type can be provided events, and will send those events to this
 //! library's event handlers.
+//!
+//! Existing applications that already use their own user event type can keep doing so, as long as
+//! it implements [`WakeupEvent`]. User events that aren't wakeups are handed back to the caller by
+//! [`Filter::handle_event`].
 
 use std::cell::Cell;
 use std::cmp;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -34,12 +39,12 @@ use std::time::Instant;
 use futures_lite::prelude::*;
 use parking::Parker;
 
-use crate::event_loop::Wakeup;
+use crate::event_loop::{Wakeup, WakeupEvent};
 use crate::reactor::Reactor;
 use crate::sync::ThreadSafety;
 
 use winit::event::Event;
-use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
 
 /// Either a function returned, or an associated future returned first.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -55,7 +60,9 @@ pub enum ReturnOrFinish<O, T> {
 ///
 /// This type takes events and passes them to the event handlers. It also handles the `async` contexts
 /// that are waiting for events.
-pub struct Filter<TS: ThreadSafety> {
+///
+/// `T` is the user event type of the event loop that the events come from.
+pub struct Filter<TS: ThreadSafety, T: WakeupEvent = Wakeup> {
     /// The deadline to wait until.
     deadline: Option<Instant>,
 
@@ -83,23 +90,26 @@ pub struct Filter<TS: ThreadSafety> {
 
     /// The reactor.
     reactor: TS::Rc<Reactor<TS>>,
+
+    /// The user event type.
+    _marker: PhantomData<fn(T) -> T>,
 }
 
-impl<TS: ThreadSafety> Filter<TS> {
+impl<TS: ThreadSafety, T: WakeupEvent> Filter<TS, T> {
     /// Create a new filter from an event loop.
     ///
     /// The future is polled once before returning to set up event handlers.
-    pub fn new(inner: &EventLoop<Wakeup>) -> Filter<TS> {
+    pub fn new(inner: &EventLoop<T>) -> Filter<TS, T> {
         let reactor = Reactor::<TS>::get();
 
         // Create a waker to wake us up.
-        let notifier = Arc::new(ReactorWaker {
-            proxy: Mutex::new(inner.create_proxy()),
-            notified: AtomicBool::new(true),
-            awake: AtomicBool::new(false),
-        });
+        let proxy = inner.create_proxy();
+        let notifier = Arc::new(ReactorWaker::new(move || {
+            proxy.send_event(T::from(Wakeup { _private: () })).ok();
+        }));
         let notifier_waker = Waker::from(notifier.clone());
         reactor.set_proxy(notifier.clone());
+        reactor.set_main_thread();
 
         // Parker/unparker pair.
         let (parker, unparker) = parking::pair();
@@ -117,21 +127,35 @@ impl<TS: ThreadSafety> Filter<TS> {
             parker_waker,
             yielding: false,
             reactor,
+            _marker: PhantomData,
         }
     }
 
     /// Handle an event.
     ///
     /// This function will block on the future if it is in the holding pattern.
+    ///
+    /// User events that aren't wakeups are not handled by the filter, and are handed back to the
+    /// caller: in [`ReturnOrFinish::Output`], or alongside the future's output in
+    /// [`ReturnOrFinish::FutureReturned`] if the future returned while handling the event.
     pub fn handle_event<F>(
         &mut self,
         future: Pin<&mut F>,
-        event: Event<Wakeup>,
-        elwt: &EventLoopWindowTarget<Wakeup>,
-    ) -> ReturnOrFinish<(), F::Output>
+        event: Event<T>,
+        elwt: &EventLoopWindowTarget<T>,
+    ) -> ReturnOrFinish<Option<T>, (F::Output, Option<T>)>
     where
         F: Future,
     {
+        // Note when the event arrived, before anything else delays it.
+        let received = self.reactor.now();
+
+        // Hand user events that aren't ours back to the caller.
+        let (event, forwarded) = match event {
+            Event::UserEvent(user) if !user.is_wakeup() => (None, Some(user)),
+            event => (Some(event), None),
+        };
+
         // Create a future that can be polled freely.
         let output = Cell::new(ReturnOrFinish::Output(()));
         let future = {
@@ -144,7 +168,7 @@ impl<TS: ThreadSafety> Filter<TS> {
 
         // Some events have special meanings.
         let about_to_sleep = match &event {
-            Event::NewEvents(_) => {
+            Some(Event::NewEvents(_)) => {
                 // Stop yielding now.
                 self.yielding = false;
 
@@ -158,59 +182,68 @@ impl<TS: ThreadSafety> Filter<TS> {
                 false
             }
 
+            Some(Event::AboutToWait) => {
+                // We are about to fall asleep.
+                self.notifier.awake.store(false, Ordering::SeqCst);
+
+                true
+            }
+
             _ => {
                 // We are not about to fall asleep.
                 false
             }
         };
 
-        // Notify the reactor with our event.
-        let notifier = self.reactor.post_event(event);
-        futures_lite::pin!(notifier);
+        // Notify the reactor with our event, if anything is listening for it.
+        if let Some(notifier) = event.and_then(|event| self.reactor.post_event(event, received)) {
+            futures_lite::pin!(notifier);
 
-        // Try to poll it once.
-        let mut cx = Context::from_waker(&self.parker_waker);
-        if notifier.as_mut().poll(&mut cx).is_pending() {
-            // We've hit a point where the future is interested, stop yielding.
-            self.yielding = false;
+            // Try to poll it once.
+            let mut cx = Context::from_waker(&self.parker_waker);
+            if notifier.as_mut().poll(&mut cx).is_pending() {
+                // We've hit a point where the future is interested, stop yielding.
+                self.yielding = false;
 
-            // Poll the future in parallel with the user's future.
-            let driver = future.as_mut().or(notifier);
-            futures_lite::pin!(driver);
+                // Poll the future in parallel with the user's future.
+                let driver = future.as_mut().or(notifier);
+                futures_lite::pin!(driver);
 
-            // Drain the request queue before anything else.
-            self.reactor.drain_loop_queue(elwt);
+                // Drain the request queue before anything else.
+                self.reactor.drain_loop_queue(elwt);
 
-            // Block on the parker/unparker pair.
-            loop {
-                if let Poll::Ready(()) = driver.as_mut().poll(&mut cx) {
-                    break;
-                }
+                // Block on the parker/unparker pair.
+                loop {
+                    if let Poll::Ready(()) = driver.as_mut().poll(&mut cx) {
+                        break;
+                    }
 
-                // Drain the incoming queue of requests.
-                self.reactor.drain_loop_queue(elwt);
+                    // Drain the incoming queue of requests.
+                    self.reactor.drain_loop_queue(elwt);
 
-                // Handle timers.
-                let deadline = {
-                    let current_deadline = self.reactor.process_timers(&mut self.wakers);
+                    // Handle timers.
+                    let deadline = {
+                        let current_deadline = self.reactor.process_timers(&mut self.wakers);
 
-                    match (current_deadline, self.deadline) {
-                        (None, None) => None,
-                        (Some(x), None) | (None, Some(x)) => Some(x),
-                        (Some(a), Some(b)) => Some(cmp::min(a, b)),
-                    }
-                };
+                        match (current_deadline, self.deadline) {
+                            (None, None) => None,
+                            (Some(x), None) | (None, Some(x)) => Some(x),
+                            (Some(a), Some(b)) => Some(cmp::min(a, b)),
+                        }
+                    };
 
-                // Wake any wakers that need to be woken.
-                for waker in self.wakers.drain(..) {
-                    waker.wake();
-                }
+                    // Wake any wakers that need to be woken.
+                    for waker in self.wakers.drain(..) {
+                        waker.wake();
+                    }
 
-                // Park the thread until it is notified, or until the timeout.
-                match deadline {
-                    None => self.parker.park(),
-                    Some(deadline) => {
-                        self.parker.park_deadline(deadline);
+                    // Park the thread until it is notified, or until the timeout.
+                    match deadline {
+                        None => self.parker.park(),
+                        Some(deadline) => {
+                            self.parker
+                                .park_deadline(self.reactor.system_time(deadline));
+                        }
                     }
                 }
             }
@@ -249,20 +282,25 @@ impl<TS: ThreadSafety> Filter<TS> {
             elwt.set_control_flow(ControlFlow::Poll);
         } else if let Some(deadline) = self.deadline {
             // The future wants to be polled again when the deadline is reached.
-            elwt.set_control_flow(ControlFlow::WaitUntil(deadline));
+            elwt.set_control_flow(ControlFlow::WaitUntil(self.reactor.system_time(deadline)));
         } else {
             // The future wants to poll.
             elwt.set_control_flow(ControlFlow::Wait);
         }
 
         // Return the output if any.
-        output.replace(ReturnOrFinish::Output(()))
+        match output.replace(ReturnOrFinish::Output(())) {
+            ReturnOrFinish::Output(()) => ReturnOrFinish::Output(forwarded),
+            ReturnOrFinish::FutureReturned(output) => {
+                ReturnOrFinish::FutureReturned((output, forwarded))
+            }
+        }
     }
 }
 
 pub(crate) struct ReactorWaker {
-    /// The proxy used to wake up the event loop.
-    proxy: Mutex<EventLoopProxy<Wakeup>>,
+    /// Sends a wakeup event through the event loop proxy.
+    proxy: Mutex<Box<dyn Fn() + Send>>,
 
     /// Whether or not we are already notified.
     notified: AtomicBool,
@@ -274,6 +312,27 @@ pub(crate) struct ReactorWaker {
 }
 
 impl ReactorWaker {
+    /// Create a waker that calls `wakeup` to wake up the event loop.
+    pub(crate) fn new(wakeup: impl Fn() + Send + 'static) -> Self {
+        Self {
+            proxy: Mutex::new(Box::new(wakeup)),
+            notified: AtomicBool::new(true),
+            awake: AtomicBool::new(false),
+        }
+    }
+
+    /// Replace the function used to wake up the event loop.
+    #[cfg(feature = "headless")]
+    pub(crate) fn set_wakeup(&self, wakeup: impl Fn() + Send + 'static) {
+        *self.proxy.lock().unwrap() = Box::new(wakeup);
+    }
+
+    /// Clear the notification, so the next wakeup calls the wakeup function again.
+    #[cfg(feature = "headless")]
+    pub(crate) fn clear_notified(&self) {
+        self.notified.store(false, Ordering::SeqCst);
+    }
+
     pub(crate) fn notify(&self) {
         // If we are already notified, don't notify again.
         if self.notified.swap(true, Ordering::SeqCst) {
@@ -286,11 +345,12 @@ impl ReactorWaker {
         }
 
         // Wake up the reactor.
-        self.proxy
-            .lock()
-            .unwrap()
-            .send_event(Wakeup { _private: () })
-            .ok();
+        self.send_wakeup();
+    }
+
+    /// Send a `Wakeup` event to the event loop, even if it is already awake.
+    pub(crate) fn send_wakeup(&self) {
+        (self.proxy.lock().unwrap())();
     }
 }
 
@@ -17,18 +17,53 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 */
 
 //! Handle incoming events.
-
-use std::cell::Cell;
+//!
+//! Every [`Waiter`] owns a [`Listener`] node. Events are delivered to the listeners one at a time,
+//! in the order they were registered. Each handoff only touches the atomic state and waker of
+//! the listener involved, so polling a waiter and notifying it never take a lock. The listeners
+//! are kept in an intrusive linked list. Adding or removing a listener only relinks its neighbours
+//! under a lock, and dispatching an event follows the links without locking.
+
+use std::cell::UnsafeCell;
 use std::future::{Future, IntoFuture};
 use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
-use std::task::{Context, Poll, Waker};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
 
 use futures_lite::{future, Stream};
-use slab::Slab;
 
-use crate::sync::{MutexGuard, ThreadSafety, __private::*};
+use crate::reactor::Reactor;
+use crate::sync::{ThreadSafety, __private::*};
+use crate::time::Timeout;
+use crate::Timer;
+
+/// The listener is waiting for an event.
+const IDLE: usize = 0;
+
+/// The listener has been handed an event that it has not taken yet.
+const NOTIFIED: usize = 1;
+
+/// The listener has taken the event, but is holding it with a [`HoldGuard`].
+const HELD: usize = 2;
+
+/// The listener is done processing the event.
+const DONE: usize = 3;
+
+/// The waiter for this listener has been dropped.
+const CLOSED: usize = 4;
+
+/// No event is being dispatched.
+const UNLOCKED: usize = 0;
+
+/// An event is being dispatched.
+const LOCKED: usize = 1;
+
+/// An event is being dispatched, and other tasks are waiting to dispatch theirs.
+const CONTENDED: usize = 2;
 
 /// An event handler.
 ///
@@ -50,14 +85,17 @@ use crate::sync::{MutexGuard, ThreadSafety, __private::*};
 ///
 /// This type does not allocate unless you use any waiting functions; therefore, you only pay overhead
 /// for events that you use.
+///
+/// Handlers can also be created with [`Handler::new`] and used as an event bus for application
+/// events, which are sent to the listeners with [`emit`](Handler::emit).
 pub struct Handler<T: Event, TS: ThreadSafety> {
     /// State of the handler.
     ///
-    /// `State` is around sixteen words plus the size of `T::Clonable`, and we store around 25 of
-    /// them per instance of `window::Registration`. In the interest of not blowing up the size of
-    /// `Registration`, we allocate this on the heap. Also, since sometimes the event will not ever
-    /// be used, we use a `OnceLock` to avoid allocating the state until it is needed.
-    state: TS::OnceLock<Box<TS::Mutex<State<T>>>>,
+    /// We store around 25 handlers per instance of `window::Registration`. In the interest of not
+    /// blowing up the size of `Registration`, we allocate this on the heap. Also, since sometimes
+    /// the event will not ever be used, we use a `OnceLock` to avoid allocating the state until it
+    /// is needed.
+    state: TS::OnceLock<Box<State<T, TS>>>,
 }
 
 impl<T: Event, TS: ThreadSafety> Drop for Handler<T, TS> {
@@ -66,37 +104,115 @@ impl<T: Event, TS: ThreadSafety> Drop for Handler<T, TS> {
     }
 }
 
-struct State<T: Event> {
-    /// Listeners for the event.
+struct State<T: Event, TS: ThreadSafety> {
+    /// The first listener for the event.
     ///
-    /// These form a linked list.
-    listeners: Slab<Listener>,
+    /// The listeners are linked together through `Listener::next`, in the order that they were
+    /// registered.
+    head: TS::Snapshot<Listener<T, TS>>,
+
+    /// The last listener for the event.
+    ///
+    /// This is locked to add or remove a listener, so only one task relinks the list at a time.
+    tail: TS::Mutex<Option<ListenerRef<T, TS>>>,
+
+    /// The number of listeners ever registered, used to number them.
+    ///
+    /// This is only increased once the new listener is linked in, so dispatchers that see the new
+    /// count are sure to reach the listener.
+    registered: TS::AtomicUsize,
 
     /// List of direct listeners.
-    directs: Vec<DirectListener<T>>,
+    directs: TS::Mutex<Vec<DirectListener<T>>>,
+
+    /// The number of waiters and direct listeners registered.
+    ///
+    /// This lets the reactor skip dispatching events that no one is listening to.
+    active: TS::AtomicUsize,
 
-    /// The head and tail of the linked list.
-    head_and_tail: Option<(usize, usize)>,
+    /// The task currently dispatching an event, waiting for a listener to finish.
+    dispatcher: TS::AtomicWaker,
 
-    /// The top-level task waiting for this task to finish.
-    waker: Option<Waker>,
+    /// Whether an event is currently being dispatched; one of `UNLOCKED`, `LOCKED` or
+    /// `CONTENDED`.
+    dispatching: TS::AtomicUsize,
 
-    /// The currently active event.
-    instance: Option<T::Clonable>,
+    /// Tasks waiting for the current dispatch to finish.
+    ///
+    /// This is only locked once the dispatch is contended.
+    blocked: TS::Mutex<Vec<Waker>>,
 }
 
-type DirectListener<T> =
-    Box<dyn FnMut(<T as Event>::Clonable) -> DirectFuture + Send + 'static>;
+type ListenerRef<T, TS> = <TS as __ThreadSafety>::Rc<Listener<T, TS>>;
+type ListenerWeak<T, TS> = <ListenerRef<T, TS> as Rc<Listener<T, TS>>>::Weak;
+type DirectListener<T> = Box<dyn FnMut(<T as Event>::Clonable) -> DirectFuture + Send + 'static>;
 type DirectFuture = Pin<Box<dyn Future<Output = bool> + Send + 'static>>;
 
+impl<T: Event, TS: ThreadSafety> Default for Handler<T, TS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Event, TS: ThreadSafety> Handler<T, TS> {
-    pub(crate) fn new() -> Self {
+    /// Create a new handler with no listeners.
+    pub fn new() -> Self {
         Self {
             state: TS::OnceLock::new(),
         }
     }
 
-    pub(crate) async fn run_with(&self, event: &mut T::Unique<'_>) {
+    /// Emit an event to the listeners of this handler.
+    ///
+    /// The event is handed to each listener in turn, the same way that events from the GUI system
+    /// are, and this completes once all of them have processed it. If another event is being
+    /// emitted, this waits for it to finish first.
+    pub async fn emit(&self, mut event: T::Unique<'_>) {
+        let timestamp = Reactor::<TS>::now_if_exists();
+        self.run_with(&mut event, timestamp).await;
+    }
+
+    /// Emit an event without waiting for the listeners to process it.
+    ///
+    /// The event is handed to every listener that is waiting for an event, and direct listeners
+    /// are called; asynchronous direct listeners that don't finish immediately are cancelled.
+    /// Since this doesn't wait, listeners that are still processing the event when the next one
+    /// is emitted will miss the next one.
+    ///
+    /// Returns the event back if another event is currently being emitted.
+    pub fn try_emit<'u>(&self, mut event: T::Unique<'u>) -> Result<(), T::Unique<'u>> {
+        // If the state hasn't been created yet, there is no one to emit to.
+        let state = match self.state.get() {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+
+        let _guard = match state.try_lock_dispatch() {
+            Some(guard) => guard,
+            None => return Err(event),
+        };
+
+        let timestamp = Reactor::<TS>::now_if_exists();
+        let event = <T as Event>::downgrade(&mut event);
+
+        // Run the direct listeners, without waiting for them.
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let direct = self.run_direct_listeners(state, event.clone());
+        futures_lite::pin!(direct);
+        if let Poll::Ready(true) = direct.as_mut().poll(&mut cx) {
+            return Ok(());
+        }
+
+        // Hand the event to every listener.
+        for listener in state.listeners() {
+            listener.notify(&event, timestamp);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn run_with(&self, event: &mut T::Unique<'_>, timestamp: Instant) {
         let event = <T as Event>::downgrade(event);
         // If the state hasn't been created yet, return.
         let state = match self.state.get() {
@@ -104,96 +220,73 @@ impl<T: Event, TS: ThreadSafety> Handler<T, TS> {
             None => return,
         };
 
+        // Only one event is dispatched at a time.
+        let _guard = state.lock_dispatch().await;
+
         // Run the direct listeners.
-        let mut state_lock = Some(state.lock().unwrap());
-        if self.run_direct_listeners(&mut state_lock, event.clone()).await {
+        if self.run_direct_listeners(state, event.clone()).await {
             return;
         }
 
-        // Set up the listeners to run.
-        {
-            let state = state_lock.get_or_insert_with(|| state.lock().unwrap());
-
-            // If there are no listeners, return.
-            let head = match state.head_and_tail {
-                Some((head, _)) => head,
-                None => return,
-            };
-
-            // Set up the state.
-            state.instance = Some(event);
-
-            // Notify the first entry in the list.
-            if let Some(waker) = state.notify(head) {
-                waker.wake();
+        // Hand the event to each listener in turn.
+        for listener in state.listeners() {
+            if !listener.notify(&event, timestamp) {
+                continue;
             }
-        }
 
-        // Wait for the listeners to finish running.
-        future::poll_fn(|cx| {
-            let mut state = state_lock.take().unwrap_or_else(|| state.lock().unwrap());
-
-            // If there are no listeners, return.
-            if state.head_and_tail.is_none() {
-                return Poll::Ready(());
-            }
+            // Wait for the listener to finish processing the event.
+            future::poll_fn(|cx| {
+                if listener.is_finished() {
+                    return Poll::Ready(());
+                }
 
-            // If the waking is over, return.
-            if state.instance.is_none() {
-                return Poll::Ready(());
-            }
+                state.dispatcher.register(cx.waker());
 
-            // If we don't need to set the waker, stop right now.
-            if let Some(waker) = &state.waker {
-                if waker.will_wake(cx.waker()) {
-                    return Poll::Pending;
+                // Check again in case we were notified in the meantime.
+                if listener.is_finished() {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
                 }
-            }
+            })
+            .await;
 
-            // Set the waker and return.
-            state.waker = Some(cx.waker().clone());
-            Poll::Pending
-        })
-        .await
+            listener
+                .state
+                .compare_exchange(DONE, IDLE, Ordering::AcqRel, Ordering::Acquire)
+                .ok();
+        }
     }
 
-    async fn run_direct_listeners(
-        &self,
-        state: &mut Option<MutexGuard<'_, State<T>, TS>>,
-        event: T::Clonable,
-    ) -> bool {
+    async fn run_direct_listeners(&self, state: &State<T, TS>, event: T::Clonable) -> bool {
         /// Guard to restore direct listeners event a
         struct RestoreDirects<'a, T: Event, TS: ThreadSafety> {
-            state: &'a Handler<T, TS>,
+            state: &'a State<T, TS>,
             directs: Vec<DirectListener<T>>,
         }
 
         impl<T: Event, TS: ThreadSafety> Drop for RestoreDirects<'_, T, TS> {
             fn drop(&mut self) {
                 let mut directs = mem::take(&mut self.directs);
-                self.state
-                    .state()
-                    .lock()
-                    .unwrap()
-                    .directs
-                    .append(&mut directs);
+                self.state.directs.lock().unwrap().append(&mut directs);
             }
         }
 
-        // If there are not indirect listeners, skip this part entirely.
-        let state_ref = state.as_mut().unwrap();
-        if state_ref.directs.is_empty() {
-            return false;
-        }
+        // Take out the direct listeners, making sure the mutex isn't locked while we call user
+        // code.
+        let mut directs = {
+            let mut state_directs = state.directs.lock().unwrap();
 
-        // Take out the direct listeners.
-        let mut directs = RestoreDirects {
-            directs: mem::take(&mut state_ref.directs),
-            state: self,
-        };
+            // If there are not indirect listeners, skip this part entirely.
+            if state_directs.is_empty() {
+                return false;
+            }
 
-        // Make sure the mutex isn't locked while we call user code.
-        *state = None;
+            RestoreDirects {
+                directs: mem::take(&mut *state_directs),
+                state,
+            }
+        };
 
         // Iterate over the direct listeners.
         for direct in &mut directs.directs {
@@ -205,11 +298,34 @@ impl<T: Event, TS: ThreadSafety> Handler<T, TS> {
         false
     }
 
+    /// Tell whether anything is listening for this event.
+    pub(crate) fn has_listeners(&self) -> bool {
+        self.state
+            .get()
+            .map_or(false, |state| state.active.load(Ordering::Acquire) > 0)
+    }
+
     /// Wait for the next event.
     pub fn wait(&self) -> Waiter<'_, T, TS> {
         Waiter::new(self)
     }
 
+    /// Wait for the next event, along with the time at which it was received.
+    pub fn wait_timestamped(&self) -> TimestampedWaiter<'_, T, TS> {
+        TimestampedWaiter {
+            waiter: self.wait(),
+        }
+    }
+
+    /// Wait for the next event, giving up after `duration`.
+    ///
+    /// This fails with [`Elapsed`] if no event arrives in time.
+    ///
+    /// [`Elapsed`]: crate::time::Elapsed
+    pub fn wait_timeout(&self, duration: Duration) -> Timeout<Waiter<'_, T, TS>, TS> {
+        Timeout::new(self.wait(), Timer::after(duration))
+    }
+
     /// Register an async closure be called when the event is received.
     pub fn wait_direct_async<
         Fut: Future<Output = bool> + Send + 'static,
@@ -218,8 +334,13 @@ impl<T: Event, TS: ThreadSafety> Handler<T, TS> {
         &self,
         mut f: F,
     ) {
-        let mut state = self.state().lock().unwrap();
-        state.directs.push(Box::new(move |u| Box::pin(f(u))))
+        let state = self.state();
+        state
+            .directs
+            .lock()
+            .unwrap()
+            .push(Box::new(move |u| Box::pin(f(u))));
+        state.active.fetch_add(1, Ordering::Release);
     }
 
     /// Register a closure be called when the event is received.
@@ -228,9 +349,19 @@ impl<T: Event, TS: ThreadSafety> Handler<T, TS> {
     }
 
     /// Get the inner state.
-    fn state(&self) -> &TS::Mutex<State<T>> {
-        self.state
-            .get_or_init(|| Box::new(TS::Mutex::new(State::new())))
+    fn state(&self) -> &State<T, TS> {
+        self.state.get_or_init(|| {
+            Box::new(State {
+                head: TS::Snapshot::new(None),
+                tail: TS::Mutex::new(None),
+                registered: TS::AtomicUsize::new(0),
+                directs: TS::Mutex::new(Vec::new()),
+                active: TS::AtomicUsize::new(0),
+                dispatcher: TS::AtomicWaker::new(),
+                dispatching: TS::AtomicUsize::new(UNLOCKED),
+                blocked: TS::Mutex::new(Vec::new()),
+            })
+        })
     }
 }
 
@@ -250,8 +381,8 @@ pub struct Waiter<'a, T: Event, TS: ThreadSafety> {
     /// The event handler.
     handler: &'a Handler<T, TS>,
 
-    /// The index of our listener.
-    index: usize,
+    /// Our listener.
+    listener: TS::Rc<Listener<T, TS>>,
 }
 
 impl<T: Event, TS: ThreadSafety> Unpin for Waiter<'_, T, TS> {}
@@ -259,55 +390,71 @@ impl<T: Event, TS: ThreadSafety> Unpin for Waiter<'_, T, TS> {}
 impl<'a, T: Event, TS: ThreadSafety> Waiter<'a, T, TS> {
     /// Create a new waiter.
     pub(crate) fn new(handler: &'a Handler<T, TS>) -> Self {
-        // Get the inner state.
+        // Insert the listener.
         let state = handler.state();
+        let listener = state.insert();
+        state.active.fetch_add(1, Ordering::Release);
 
-        // Insert the listener.
-        let index = state.lock().unwrap().insert();
-        Self { handler, index }
+        Self { handler, listener }
     }
 
-    fn notify_next(&mut self, mut state: MutexGuard<'_, State<T>, TS>) {
-        if let Some(next) = state.listeners[self.index].next.get() {
-            // Notify the next listener.
-            if let Some(waker) = state.notify(next) {
-                waker.wake();
-            }
-        } else {
-            // We're done with the chain, notify the top-level task.
-            state.instance = None;
-            if let Some(waker) = state.waker.take() {
-                waker.wake();
+    /// Wait until we are notified, then take the event.
+    fn poll_event(&self, cx: &mut Context<'_>) -> Poll<Timestamped<T::Clonable>> {
+        let listener = &self.listener;
+
+        if listener.state.load(Ordering::Acquire) != NOTIFIED {
+            // Register the waker and check again in case we were notified in the meantime.
+            listener.waker.register(cx.waker());
+            if listener.state.load(Ordering::Acquire) != NOTIFIED {
+                return Poll::Pending;
             }
         }
+
+        // SAFETY: We were notified, so the dispatcher is done writing the event.
+        let event =
+            unsafe { listener.take_event() }.expect("listener was notified without an event");
+        Poll::Ready(event)
+    }
+
+    /// Take the next event and let the next listener in the chain run.
+    fn poll_timestamped(&self, cx: &mut Context<'_>) -> Poll<Option<Timestamped<T::Clonable>>> {
+        let event = match self.poll_event(cx) {
+            Poll::Ready(event) => event,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        // Let the next listener in the chain run.
+        self.finish();
+
+        Poll::Ready(Some(event))
+    }
+
+    /// Tell the dispatcher that we are done with the current event.
+    fn finish(&self) {
+        self.listener.state.store(DONE, Ordering::Release);
+        self.handler.state().dispatcher.wake();
+    }
+
+    /// Wait for the next event, giving up after `duration`.
+    ///
+    /// This fails with [`Elapsed`] if no event arrives in time. The waiter can still be used
+    /// afterwards.
+    ///
+    /// [`Elapsed`]: crate::time::Elapsed
+    pub fn next_within(&mut self, duration: Duration) -> Timeout<&mut Self, TS> {
+        Timeout::new(self, Timer::after(duration))
     }
 
     /// Wait for a guard that prevents the event from moving on.
     pub async fn hold(&mut self) -> HoldGuard<'_, 'a, T, TS> {
         // Wait for the event.
-        let event = future::poll_fn(|cx| {
-            let mut state = self.handler.state().lock().unwrap();
-
-            // See if we are notified.
-            if state.take_notification(self.index) {
-                let event = match state.instance.clone() {
-                    Some(event) => event,
-                    None => return Poll::Pending,
-                };
-
-                // Return the event.
-                return Poll::Ready(event);
-            }
-
-            // Register the waker and sleep.
-            state.register_waker(self.index, cx.waker());
-            Poll::Pending
-        })
-        .await;
+        let Timestamped { event, timestamp } = future::poll_fn(|cx| self.poll_event(cx)).await;
+        self.listener.state.store(HELD, Ordering::Release);
 
         HoldGuard {
             waiter: self,
             event: Some(event),
+            timestamp,
         }
     }
 }
@@ -327,27 +474,68 @@ impl<T: Event, TS: ThreadSafety> Future for Waiter<'_, T, TS> {
 impl<T: Event, TS: ThreadSafety> Stream for Waiter<'_, T, TS> {
     type Item = T::Clonable;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let mut state = self.handler.state.get().unwrap().lock().unwrap();
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_timestamped(cx)
+            .map(|event| event.map(|event| event.event))
+    }
 
-        // See if we are notified.
-        if state.take_notification(self.index) {
-            let event = match state.instance.clone() {
-                Some(event) => event,
-                None => return Poll::Pending,
-            };
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
 
-            // Notify the next listener in the chain.
-            self.notify_next(state);
+impl<'a, T: Event, TS: ThreadSafety> Drop for Waiter<'a, T, TS> {
+    fn drop(&mut self) {
+        let state = self.handler.state();
 
-            // Return the event.
-            return Poll::Ready(Some(event));
+        // Remove the listener.
+        state.remove(&self.listener);
+        state.active.fetch_sub(1, Ordering::Release);
+
+        // Let the next listener run if the dispatcher is waiting on us.
+        let old = self.listener.state.swap(CLOSED, Ordering::AcqRel);
+        if old == NOTIFIED || old == HELD {
+            // SAFETY: We were notified, so the dispatcher is done writing the event.
+            unsafe { self.listener.take_event() };
+            state.dispatcher.wake();
         }
+    }
+}
+
+/// Waits for events, along with the time at which they were received.
+///
+/// This is returned by [`Handler::wait_timestamped`].
+pub struct TimestampedWaiter<'a, T: Event, TS: ThreadSafety> {
+    /// The underlying waiter.
+    waiter: Waiter<'a, T, TS>,
+}
 
-        // Register the waker.
-        state.register_waker(self.index, cx.waker());
+impl<T: Event, TS: ThreadSafety> Unpin for TimestampedWaiter<'_, T, TS> {}
 
-        Poll::Pending
+impl<'a, T: Event, TS: ThreadSafety> TimestampedWaiter<'a, T, TS> {
+    /// Get the underlying waiter.
+    pub fn into_inner(self) -> Waiter<'a, T, TS> {
+        self.waiter
+    }
+}
+
+impl<T: Event, TS: ThreadSafety> Future for TimestampedWaiter<'_, T, TS> {
+    type Output = Timestamped<T::Clonable>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.poll_next(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(event),
+            Poll::Ready(None) => panic!("event handler was dropped"),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: Event, TS: ThreadSafety> Stream for TimestampedWaiter<'_, T, TS> {
+    type Item = Timestamped<T::Clonable>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.waiter.poll_timestamped(cx)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -355,17 +543,24 @@ impl<T: Event, TS: ThreadSafety> Stream for Waiter<'_, T, TS> {
     }
 }
 
-impl<'a, T: Event, TS: ThreadSafety> Drop for Waiter<'a, T, TS> {
-    fn drop(&mut self) {
-        let mut state = self.handler.state().lock().unwrap();
-
-        // Remove the listener.
-        let listener = state.remove(self.index);
+/// An event, along with the time at which it was received.
+///
+/// The timestamp is taken from the reactor's clock when the event loop hands the event to
+/// `async-winit`, before it is dispatched to any listener. This makes it suitable for measuring
+/// input latency or detecting double clicks, no matter how late the listener gets to the event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timestamped<T> {
+    /// The event.
+    pub event: T,
+
+    /// The time at which the event was received.
+    pub timestamp: Instant,
+}
 
-        // Notify the next listener if we are notified.
-        if listener.notified.get() {
-            self.notify_next(state);
-        }
+impl<T> Timestamped<T> {
+    /// Get the event, discarding the timestamp.
+    pub fn into_inner(self) -> T {
+        self.event
     }
 }
 
@@ -376,6 +571,9 @@ pub struct HoldGuard<'waiter, 'handler, T: Event, TS: ThreadSafety> {
 
     /// The event we just received.
     event: Option<T::Clonable>,
+
+    /// The time at which the event was received.
+    timestamp: Instant,
 }
 
 impl<T: Event, TS: ThreadSafety> Deref for HoldGuard<'_, '_, T, TS> {
@@ -397,134 +595,254 @@ impl<T: Event, TS: ThreadSafety> HoldGuard<'_, '_, T, TS> {
     pub fn into_inner(mut self) -> T::Clonable {
         self.event.take().unwrap()
     }
+
+    /// Get the time at which the event was received.
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
 }
 
 impl<T: Event, TS: ThreadSafety> Drop for HoldGuard<'_, '_, T, TS> {
     fn drop(&mut self) {
         // Tell the waiter to notify the next listener.
-        self.waiter
-            .notify_next(self.waiter.handler.state().lock().unwrap());
+        self.waiter.finish();
     }
 }
 
-impl<T: Event> State<T> {
-    /// Get a fresh state instance.
-    fn new() -> Self {
-        Self {
-            listeners: Slab::new(),
-            directs: Vec::new(),
-            head_and_tail: None,
-            waker: None,
-            instance: None,
-        }
-    }
+/// A registered listener in the event handler.
+struct Listener<T: Event, TS: ThreadSafety> {
+    /// The state of the listener; one of `IDLE`, `NOTIFIED`, `HELD`, `DONE` or `CLOSED`.
+    state: TS::AtomicUsize,
 
-    /// Insert a new listener into the list.
-    fn insert(&mut self) -> usize {
-        // Create the listener.
-        let listener = Listener {
-            next: Cell::new(None),
-            prev: Cell::new(self.head_and_tail.map(|(_, tail)| tail)),
-            waker: Cell::new(None),
-            notified: Cell::new(false),
-        };
+    /// The waker for the listener.
+    waker: TS::AtomicWaker,
 
-        // Insert the listener into the list.
-        let index = self.listeners.insert(listener);
+    /// The event handed to this listener.
+    ///
+    /// The dispatcher only writes this while the listener is `IDLE`, and the listener only takes
+    /// it after being notified, so the two never access it at the same time.
+    event: UnsafeCell<Option<Timestamped<T::Clonable>>>,
 
-        // Update the head and tail.
-        match &mut self.head_and_tail {
-            Some((_head, tail)) => {
-                self.listeners[*tail].next.set(Some(index));
-                *tail = index;
-            }
+    /// The number of listeners registered before this one.
+    index: usize,
 
-            None => {
-                self.head_and_tail = Some((index, index));
-            }
-        }
+    /// The next listener in the list.
+    ///
+    /// This is left alone when the listener is removed, so dispatchers that are still holding it
+    /// can move on to the rest of the list.
+    next: TS::Snapshot<Listener<T, TS>>,
 
-        index
-    }
+    /// The previous listener in the list, or `None` if this is the first one.
+    ///
+    /// This is only used while `State::tail` is locked.
+    prev: TS::Mutex<Option<ListenerWeak<T, TS>>>,
+}
 
-    /// Remove a listener from the list.
-    fn remove(&mut self, index: usize) -> Listener {
-        // Get the listener.
-        let listener = self.listeners.remove(index);
+// SAFETY: `event` is only accessed by one thread at a time, as described above.
+#[cfg(feature = "thread_safe")]
+unsafe impl<T: Event> Send for Listener<T, crate::sync::ThreadSafe> where T::Clonable: Send {}
+#[cfg(feature = "thread_safe")]
+unsafe impl<T: Event> Sync for Listener<T, crate::sync::ThreadSafe> where T::Clonable: Send {}
 
-        // Update the head and tail.
-        match &mut self.head_and_tail {
-            Some((head, tail)) => {
-                if *head == index && *tail == index {
-                    self.head_and_tail = None;
-                } else if *head == index {
-                    self.head_and_tail = Some((listener.next.get().unwrap(), *tail));
-                } else if *tail == index {
-                    self.head_and_tail = Some((*head, listener.prev.get().unwrap()));
-                }
-            }
+impl<T: Event, TS: ThreadSafety> Listener<T, TS> {
+    /// Hand an event to this listener.
+    ///
+    /// Returns `false` if the listener can't take the event.
+    fn notify(&self, event: &T::Clonable, timestamp: Instant) -> bool {
+        // A previous dispatch may have been cancelled after this listener finished.
+        self.state
+            .compare_exchange(DONE, IDLE, Ordering::AcqRel, Ordering::Acquire)
+            .ok();
 
-            None => panic!("invalid listener list: head and tail are both None"),
+        // Skip listeners that are closed, or still busy with an event from a cancelled dispatch.
+        if self.state.load(Ordering::Acquire) != IDLE {
+            return false;
         }
 
-        // Update the next and previous listeners.
-        if let Some(next) = listener.next.get() {
-            self.listeners[next].prev.set(listener.prev.get());
+        // Store the event, then make it visible to the listener.
+        // SAFETY: The listener is idle, so it won't touch the event until it is notified.
+        unsafe {
+            *self.event.get() = Some(Timestamped {
+                event: event.clone(),
+                timestamp,
+            });
+        }
+        if self
+            .state
+            .compare_exchange(IDLE, NOTIFIED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // The listener was closed in the meantime, without looking at the event.
+            // SAFETY: The listener is closed, so it will never touch the event again.
+            unsafe { self.take_event() };
+            return false;
         }
+        self.waker.wake();
+
+        true
+    }
+
+    /// Tell whether the dispatcher can move on to the next listener.
+    fn is_finished(&self) -> bool {
+        matches!(self.state.load(Ordering::Acquire), DONE | CLOSED)
+    }
+
+    /// Take the event handed to this listener.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to the event: either the listener was notified and
+    /// this is the waiter, or the dispatcher failed to notify a closed listener.
+    unsafe fn take_event(&self) -> Option<Timestamped<T::Clonable>> {
+        (*self.event.get()).take()
+    }
+}
 
-        if let Some(prev) = listener.prev.get() {
-            self.listeners[prev].next.set(listener.next.get());
+impl<T: Event, TS: ThreadSafety> State<T, TS> {
+    /// Add a new listener to the end of the list.
+    fn insert(&self) -> ListenerRef<T, TS> {
+        let mut tail = self.tail.lock().unwrap();
+        let index = self.registered.load(Ordering::Acquire);
+
+        let listener = TS::Rc::new(Listener {
+            state: TS::AtomicUsize::new(IDLE),
+            waker: TS::AtomicWaker::new(),
+            event: UnsafeCell::new(None),
+            index,
+            next: TS::Snapshot::new(None),
+            prev: TS::Mutex::new(tail.as_ref().map(Rc::downgrade)),
+        });
+
+        match &*tail {
+            Some(prev) => prev.next.store(Some(listener.clone())),
+            None => self.head.store(Some(listener.clone())),
         }
+        *tail = Some(listener.clone());
+        self.registered
+            .store(index.wrapping_add(1), Ordering::Release);
 
         listener
     }
 
-    /// Take out the notification.
-    fn take_notification(&mut self, index: usize) -> bool {
-        self.listeners[index].notified.replace(false)
+    /// Unlink a listener from the list.
+    fn remove(&self, listener: &ListenerRef<T, TS>) {
+        let mut tail = self.tail.lock().unwrap();
+
+        // Neighbours are still in the list, so they are kept alive by their own neighbours.
+        let prev: Option<ListenerRef<T, TS>> = listener
+            .prev
+            .lock()
+            .unwrap()
+            .take()
+            .and_then(|prev| Rc::upgrade(&prev));
+        let next = listener.next.load();
+
+        match &prev {
+            Some(prev) => prev.next.store(next.clone()),
+            None => self.head.store(next.clone()),
+        }
+        match &next {
+            Some(next) => *next.prev.lock().unwrap() = prev.as_ref().map(Rc::downgrade),
+            None => *tail = prev,
+        }
     }
 
-    /// Register a waker.
-    fn register_waker(&mut self, index: usize, waker: &Waker) {
-        let listener = &mut self.listeners[index];
+    /// Iterate over the listeners registered so far.
+    fn listeners(&self) -> Listeners<T, TS> {
+        // Read the count first, so that every listener it includes is reachable from the head.
+        let end = self.registered.load(Ordering::Acquire);
+        Listeners {
+            next: self.head.load(),
+            end,
+        }
+    }
 
-        // If the listener's waker is the same as ours, no need to clone.
-        let current_waker = listener.waker.take();
-        match current_waker {
-            Some(current_waker) if current_waker.will_wake(waker) => {
-                listener.waker.replace(Some(current_waker));
+    /// Wait until no other event is being dispatched.
+    async fn lock_dispatch(&self) -> DispatchGuard<'_, T, TS> {
+        future::poll_fn(|cx| loop {
+            if let Some(guard) = self.try_lock_dispatch() {
+                return Poll::Ready(guard);
             }
-            _ => {
-                listener.waker.replace(Some(waker.clone()));
+
+            let mut blocked = self.blocked.lock().unwrap();
+            if !blocked.iter().any(|w| w.will_wake(cx.waker())) {
+                blocked.push(cx.waker().clone());
             }
-        }
+
+            // Tell the dispatcher to wake us up, unless the dispatch finished in the meantime.
+            match self.dispatching.compare_exchange(
+                LOCKED,
+                CONTENDED,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) | Err(CONTENDED) => return Poll::Pending,
+                Err(_) => continue,
+            }
+        })
+        .await
     }
 
-    /// Notify the listener.
-    fn notify(&mut self, index: usize) -> Option<Waker> {
-        // If the listener is already notified, return.
-        if self.listeners[index].notified.replace(true) {
+    /// Start dispatching an event, if no other event is being dispatched.
+    fn try_lock_dispatch(&self) -> Option<DispatchGuard<'_, T, TS>> {
+        self.dispatching
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| DispatchGuard { state: self })
+    }
+}
+
+/// Iterates over the listeners of a handler.
+///
+/// Listeners that are registered during the iteration are skipped, so that a listener that waits
+/// again after taking an event doesn't get the same event twice.
+struct Listeners<T: Event, TS: ThreadSafety> {
+    /// The next listener to visit.
+    next: Option<ListenerRef<T, TS>>,
+
+    /// The number of listeners that were registered when the iteration started.
+    end: usize,
+}
+
+impl<T: Event, TS: ThreadSafety> Iterator for Listeners<T, TS> {
+    type Item = ListenerRef<T, TS>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let listener = self.next.take()?;
+
+        // Compare the distance rather than the indices themselves, in case they wrapped around.
+        if self.end.wrapping_sub(listener.index) as isize <= 0 {
             return None;
         }
 
-        // Return the waker.
-        self.listeners[index].waker.replace(None)
+        self.next = listener.next.load();
+        Some(listener)
     }
 }
 
-/// A registered listener in the event handler.
-struct Listener {
-    /// The next listener in the list.
-    next: Cell<Option<usize>>,
+/// Lets other events be dispatched once dropped.
+struct DispatchGuard<'a, T: Event, TS: ThreadSafety> {
+    state: &'a State<T, TS>,
+}
+
+impl<T: Event, TS: ThreadSafety> Drop for DispatchGuard<'_, T, TS> {
+    fn drop(&mut self) {
+        if self.state.dispatching.swap(UNLOCKED, Ordering::AcqRel) != CONTENDED {
+            return;
+        }
 
-    /// The previous listener in the list.
-    prev: Cell<Option<usize>>,
+        let blocked = mem::take(&mut *self.state.blocked.lock().unwrap());
+        for waker in blocked {
+            waker.wake();
+        }
+    }
+}
 
-    /// The waker for the listener.
-    waker: Cell<Option<Waker>>,
+/// A waker that does nothing.
+struct NoopWaker;
 
-    /// Whether or not this listener is notified.
-    notified: Cell<bool>,
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
 }
 
 /// The type of event that can be sent over a [`Handler`].
@@ -543,13 +861,3 @@ impl<T: Clone + 'static> Event for T {
         unique.clone()
     }
 }
-
-/*
-struct CallOnDrop<F: FnMut()>(F);
-
-impl<F: FnMut()> Drop for CallOnDrop<F> {
-    fn drop(&mut self) {
-        (self.0)();
-    }
-}
-*/
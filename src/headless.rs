@@ -0,0 +1,646 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! A mock backend for running without a display.
+//!
+//! An event loop created with [`EventLoopBuilder::build_headless`] doesn't connect to the windowing
+//! system. It never fails to build, and every [`Window`] created while it runs is backed by a
+//! [`HeadlessWindow`], which keeps the window's state (its size, title, visibility, focus and so
+//! on) in memory. Window operations are applied to that state, so the same code that drives real
+//! windows can be run in CI and in unit tests. Event loops created with
+//! [`EventLoopBuilder::build`] are unaffected by the `headless` feature.
+//!
+//! Nothing happens on its own in a headless event loop. Events are delivered with
+//! [`Window::inject`] and [`EventLoopWindowTarget::inject_lifecycle`], and timers are best driven
+//! by a [`ManualClock`] passed to [`EventLoopBuilder::with_clock`]. Some other differences:
+//!
+//! - Changing the state of a window doesn't generate any events. Injected `Resized`, `Moved`,
+//!   `Focused` and `ThemeChanged` events update the state of the window.
+//! - Redraw requests are counted by [`HeadlessWindow::redraw_requests`], but `RedrawRequested`
+//!   events are only delivered if they are injected.
+//! - There are no monitors and the scale factor is always `1.0`.
+//! - Windows have no raw handles, and [`Window::window`] panics. Use [`Window::headless_window`]
+//!   instead.
+//! - A [`WindowBuilder`] created from a `winit` builder ignores it, since its attributes can't be
+//!   read back.
+//!
+//! [`EventLoopExtRunOnDemand::block_on_demand`] returns the output of the future, which makes it
+//! the most convenient way to run a test:
+//!
+//! ```no_run
+//! use async_winit::event_loop::EventLoopBuilder;
+//! use async_winit::platform::run_return::EventLoopExtRunOnDemand;
+//! use async_winit::window::Window;
+//! use async_winit::ThreadUnsafe;
+//!
+//! let mut event_loop = EventLoopBuilder::new().build_headless::<ThreadUnsafe>();
+//! event_loop.block_on_demand(async {
+//!     let window = Window::<ThreadUnsafe>::new().await.unwrap();
+//!     window.set_title("Hello").await;
+//!     assert_eq!(window.headless_window().unwrap().title(), "Hello");
+//! });
+//! ```
+//!
+//! This module is only available with the `headless` feature.
+//!
+//! [`EventLoopBuilder::build`]: crate::event_loop::EventLoopBuilder::build
+//! [`EventLoopBuilder::build_headless`]: crate::event_loop::EventLoopBuilder::build_headless
+//! [`EventLoopBuilder::with_clock`]: crate::event_loop::EventLoopBuilder::with_clock
+//! [`EventLoopWindowTarget::inject_lifecycle`]: crate::event_loop::EventLoopWindowTarget::inject_lifecycle
+//! [`EventLoopExtRunOnDemand::block_on_demand`]: crate::platform::run_return::EventLoopExtRunOnDemand::block_on_demand
+//! [`ManualClock`]: crate::ManualClock
+//! [`Window`]: crate::window::Window
+//! [`Window::headless_window`]: crate::window::Window::headless_window
+//! [`Window::inject`]: crate::window::Window::inject
+//! [`Window::window`]: crate::window::Window::window
+//! [`WindowBuilder`]: crate::window::WindowBuilder
+
+use crate::filter::ReactorWaker;
+use crate::reactor::Reactor;
+use crate::sync::ThreadSafety;
+use crate::window::WindowAttributes;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use winit::dpi::{PhysicalPosition, PhysicalSize, Position, Size};
+use winit::error::{ExternalError, NotSupportedError};
+use winit::event::{Event, WindowEvent};
+use winit::monitor::MonitorHandle;
+use winit::window::{
+    CursorGrabMode, CursorIcon, Fullscreen, Icon, ImePurpose, ResizeDirection, Theme,
+    UserAttentionType, WindowButtons, WindowId, WindowLevel,
+};
+
+/// The scale factor of every headless window.
+const SCALE_FACTOR: f64 = 1.0;
+
+/// The size of a window that doesn't request one.
+const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(800, 600);
+
+/// The ID of the next window to be created.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A window that only exists in memory.
+///
+/// This is what [`Window::headless_window`] returns for windows created by a headless event loop.
+/// It has the same methods as a `winit` window, along with a few more to inspect state that
+/// `winit` can't read back.
+///
+/// [`Window::headless_window`]: crate::window::Window::headless_window
+#[derive(Debug)]
+pub struct HeadlessWindow {
+    /// The ID of the window.
+    id: WindowId,
+
+    /// The state of the window.
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    title: String,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    min_size: Option<PhysicalSize<u32>>,
+    max_size: Option<PhysicalSize<u32>>,
+    resize_increments: Option<PhysicalSize<u32>>,
+    visible: bool,
+    resizable: bool,
+    enabled_buttons: WindowButtons,
+    minimized: bool,
+    maximized: bool,
+    fullscreen: Option<Fullscreen>,
+    decorated: bool,
+    transparent: bool,
+    window_level: WindowLevel,
+    window_icon: Option<Icon>,
+    theme: Option<Theme>,
+    content_protected: bool,
+    focused: bool,
+    user_attention: Option<UserAttentionType>,
+    ime_allowed: bool,
+    ime_purpose: ImePurpose,
+    cursor_icon: CursorIcon,
+    cursor_grab: CursorGrabMode,
+    cursor_visible: bool,
+    cursor_hittest: bool,
+    redraw_requests: usize,
+}
+
+impl HeadlessWindow {
+    /// Create a window from a set of attributes.
+    pub(crate) fn new(attributes: &WindowAttributes) -> Self {
+        let min_size = attributes.min_inner_size.map(to_physical_size);
+        let max_size = attributes.max_inner_size.map(to_physical_size);
+        let size = attributes.inner_size.map_or(DEFAULT_SIZE, to_physical_size);
+
+        Self {
+            id: WindowId::from(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+            state: Mutex::new(State {
+                title: attributes.title.clone(),
+                position: attributes
+                    .position
+                    .map_or(PhysicalPosition::new(0, 0), to_physical_position),
+                size: clamp(size, min_size, max_size),
+                min_size,
+                max_size,
+                resize_increments: attributes.resize_increments.map(to_physical_size),
+                visible: attributes.visible,
+                resizable: attributes.resizable,
+                enabled_buttons: attributes.enabled_buttons,
+                minimized: false,
+                maximized: attributes.maximized,
+                fullscreen: attributes.fullscreen.clone(),
+                decorated: attributes.decorations,
+                transparent: attributes.transparent,
+                window_level: attributes.window_level,
+                window_icon: attributes.window_icon.clone(),
+                theme: attributes.preferred_theme,
+                content_protected: attributes.content_protected,
+                focused: false,
+                user_attention: None,
+                ime_allowed: false,
+                ime_purpose: ImePurpose::Normal,
+                cursor_icon: CursorIcon::Default,
+                cursor_grab: CursorGrabMode::None,
+                cursor_visible: true,
+                cursor_hittest: true,
+                redraw_requests: 0,
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Update the state of the window for an event that was injected into it.
+    pub(crate) fn observe(&self, event: &WindowEvent) {
+        let mut state = self.state();
+        match event {
+            WindowEvent::Resized(size) => state.size = *size,
+            WindowEvent::Moved(position) => state.position = *position,
+            WindowEvent::Focused(focused) => state.focused = *focused,
+            WindowEvent::ThemeChanged(theme) => state.theme = Some(*theme),
+            _ => {}
+        }
+    }
+
+    /// Get the ID of the window.
+    pub fn id(&self) -> WindowId {
+        self.id
+    }
+
+    /// Get the scale factor of the window, which is always `1.0`.
+    pub fn scale_factor(&self) -> f64 {
+        SCALE_FACTOR
+    }
+
+    /// Request a redraw.
+    ///
+    /// This only increments [`redraw_requests`](Self::redraw_requests).
+    pub fn request_redraw(&self) {
+        self.state().redraw_requests += 1;
+    }
+
+    /// Get the number of times that a redraw has been requested.
+    pub fn redraw_requests(&self) -> usize {
+        self.state().redraw_requests
+    }
+
+    /// Get the position of the window's client area.
+    pub fn inner_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        Ok(self.state().position)
+    }
+
+    /// Get the position of the window.
+    pub fn outer_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        Ok(self.state().position)
+    }
+
+    /// Move the window.
+    pub fn set_outer_position<P: Into<Position>>(&self, position: P) {
+        self.state().position = to_physical_position(position.into());
+    }
+
+    /// Get the size of the window's client area.
+    pub fn inner_size(&self) -> PhysicalSize<u32> {
+        self.state().size
+    }
+
+    /// Resize the window, within its size constraints.
+    ///
+    /// The request is always applied immediately.
+    pub fn request_inner_size<S: Into<Size>>(&self, size: S) -> Option<PhysicalSize<u32>> {
+        let mut state = self.state();
+        state.size = clamp(
+            to_physical_size(size.into()),
+            state.min_size,
+            state.max_size,
+        );
+        Some(state.size)
+    }
+
+    /// Get the size of the window, which is the same as its inner size.
+    pub fn outer_size(&self) -> PhysicalSize<u32> {
+        self.state().size
+    }
+
+    /// Get the minimum inner size of the window.
+    pub fn min_inner_size(&self) -> Option<PhysicalSize<u32>> {
+        self.state().min_size
+    }
+
+    /// Set the minimum inner size of the window.
+    pub fn set_min_inner_size<S: Into<Size>>(&self, min_size: Option<S>) {
+        let mut state = self.state();
+        state.min_size = min_size.map(|size| to_physical_size(size.into()));
+        state.size = clamp(state.size, state.min_size, state.max_size);
+    }
+
+    /// Get the maximum inner size of the window.
+    pub fn max_inner_size(&self) -> Option<PhysicalSize<u32>> {
+        self.state().max_size
+    }
+
+    /// Set the maximum inner size of the window.
+    pub fn set_max_inner_size<S: Into<Size>>(&self, max_size: Option<S>) {
+        let mut state = self.state();
+        state.max_size = max_size.map(|size| to_physical_size(size.into()));
+        state.size = clamp(state.size, state.min_size, state.max_size);
+    }
+
+    /// Get the resize increments of the window.
+    pub fn resize_increments(&self) -> Option<PhysicalSize<u32>> {
+        self.state().resize_increments
+    }
+
+    /// Set the resize increments of the window.
+    pub fn set_resize_increments<S: Into<Size>>(&self, increments: Option<S>) {
+        self.state().resize_increments = increments.map(|size| to_physical_size(size.into()));
+    }
+
+    /// Get the title of the window.
+    pub fn title(&self) -> String {
+        self.state().title.clone()
+    }
+
+    /// Set the title of the window.
+    pub fn set_title(&self, title: &str) {
+        self.state().title = title.to_string();
+    }
+
+    /// Get whether the window is transparent.
+    pub fn transparent(&self) -> bool {
+        self.state().transparent
+    }
+
+    /// Set whether the window is transparent.
+    pub fn set_transparent(&self, transparent: bool) {
+        self.state().transparent = transparent;
+    }
+
+    /// Get whether the window is visible.
+    pub fn is_visible(&self) -> Option<bool> {
+        Some(self.state().visible)
+    }
+
+    /// Set whether the window is visible.
+    pub fn set_visible(&self, visible: bool) {
+        self.state().visible = visible;
+    }
+
+    /// Get whether the window is resizable.
+    pub fn is_resizable(&self) -> bool {
+        self.state().resizable
+    }
+
+    /// Set whether the window is resizable.
+    pub fn set_resizable(&self, resizable: bool) {
+        self.state().resizable = resizable;
+    }
+
+    /// Get the enabled window buttons.
+    pub fn enabled_buttons(&self) -> WindowButtons {
+        self.state().enabled_buttons
+    }
+
+    /// Set the enabled window buttons.
+    pub fn set_enabled_buttons(&self, buttons: WindowButtons) {
+        self.state().enabled_buttons = buttons;
+    }
+
+    /// Get whether the window is minimized.
+    pub fn is_minimized(&self) -> Option<bool> {
+        Some(self.state().minimized)
+    }
+
+    /// Set whether the window is minimized.
+    pub fn set_minimized(&self, minimized: bool) {
+        self.state().minimized = minimized;
+    }
+
+    /// Get whether the window is maximized.
+    pub fn is_maximized(&self) -> bool {
+        self.state().maximized
+    }
+
+    /// Set whether the window is maximized.
+    pub fn set_maximized(&self, maximized: bool) {
+        self.state().maximized = maximized;
+    }
+
+    /// Get the fullscreen state of the window.
+    pub fn fullscreen(&self) -> Option<Fullscreen> {
+        self.state().fullscreen.clone()
+    }
+
+    /// Set the fullscreen state of the window.
+    pub fn set_fullscreen(&self, fullscreen: Option<Fullscreen>) {
+        self.state().fullscreen = fullscreen;
+    }
+
+    /// Get whether the window is decorated.
+    pub fn is_decorated(&self) -> bool {
+        self.state().decorated
+    }
+
+    /// Set whether the window is decorated.
+    pub fn set_decorations(&self, decorations: bool) {
+        self.state().decorated = decorations;
+    }
+
+    /// Get the level of the window.
+    pub fn window_level(&self) -> WindowLevel {
+        self.state().window_level
+    }
+
+    /// Set the level of the window.
+    pub fn set_window_level(&self, level: WindowLevel) {
+        self.state().window_level = level;
+    }
+
+    /// Get the icon of the window.
+    pub fn window_icon(&self) -> Option<Icon> {
+        self.state().window_icon.clone()
+    }
+
+    /// Set the icon of the window.
+    pub fn set_window_icon(&self, window_icon: Option<Icon>) {
+        self.state().window_icon = window_icon;
+    }
+
+    /// Get the theme of the window.
+    pub fn theme(&self) -> Option<Theme> {
+        self.state().theme
+    }
+
+    /// Set the theme of the window.
+    pub fn set_theme(&self, theme: Option<Theme>) {
+        self.state().theme = theme;
+    }
+
+    /// Get whether the contents of the window are protected from being captured.
+    pub fn is_content_protected(&self) -> bool {
+        self.state().content_protected
+    }
+
+    /// Set whether the contents of the window are protected from being captured.
+    pub fn set_content_protected(&self, protected: bool) {
+        self.state().content_protected = protected;
+    }
+
+    /// Get whether the window has focus.
+    pub fn has_focus(&self) -> bool {
+        self.state().focused
+    }
+
+    /// Give the window focus.
+    ///
+    /// This doesn't deliver a `Focused` event, or take focus away from other windows.
+    pub fn focus_window(&self) {
+        self.state().focused = true;
+    }
+
+    /// Get the last request for the user's attention.
+    pub fn user_attention(&self) -> Option<UserAttentionType> {
+        self.state().user_attention
+    }
+
+    /// Request the user's attention.
+    pub fn request_user_attention(&self, request_type: Option<UserAttentionType>) {
+        self.state().user_attention = request_type;
+    }
+
+    /// Get whether IME is allowed.
+    pub fn ime_allowed(&self) -> bool {
+        self.state().ime_allowed
+    }
+
+    /// Set whether IME is allowed.
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.state().ime_allowed = allowed;
+    }
+
+    /// Get the IME purpose.
+    pub fn ime_purpose(&self) -> ImePurpose {
+        self.state().ime_purpose
+    }
+
+    /// Set the IME purpose.
+    pub fn set_ime_purpose(&self, purpose: ImePurpose) {
+        self.state().ime_purpose = purpose;
+    }
+
+    /// Set the IME cursor area. This does nothing.
+    pub fn set_ime_cursor_area<P: Into<Position>, S: Into<Size>>(&self, position: P, size: S) {
+        let _ = (position, size);
+    }
+
+    /// Get the cursor icon.
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.state().cursor_icon
+    }
+
+    /// Set the cursor icon.
+    pub fn set_cursor_icon(&self, cursor: CursorIcon) {
+        self.state().cursor_icon = cursor;
+    }
+
+    /// Move the cursor. This does nothing.
+    pub fn set_cursor_position<P: Into<Position>>(&self, position: P) -> Result<(), ExternalError> {
+        let _ = position;
+        Ok(())
+    }
+
+    /// Get the cursor grab mode.
+    pub fn cursor_grab(&self) -> CursorGrabMode {
+        self.state().cursor_grab
+    }
+
+    /// Set the cursor grab mode.
+    pub fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        self.state().cursor_grab = mode;
+        Ok(())
+    }
+
+    /// Get whether the cursor is visible.
+    pub fn is_cursor_visible(&self) -> bool {
+        self.state().cursor_visible
+    }
+
+    /// Set whether the cursor is visible.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.state().cursor_visible = visible;
+    }
+
+    /// Get whether the window receives cursor events.
+    pub fn cursor_hittest(&self) -> bool {
+        self.state().cursor_hittest
+    }
+
+    /// Set whether the window receives cursor events.
+    pub fn set_cursor_hittest(&self, hittest: bool) -> Result<(), ExternalError> {
+        self.state().cursor_hittest = hittest;
+        Ok(())
+    }
+
+    /// Start dragging the window. This does nothing.
+    pub fn drag_window(&self) -> Result<(), ExternalError> {
+        Ok(())
+    }
+
+    /// Start resizing the window. This does nothing.
+    pub fn drag_resize_window(&self, direction: ResizeDirection) -> Result<(), ExternalError> {
+        let _ = direction;
+        Ok(())
+    }
+
+    /// Get the monitor that the window is on, which is always `None`.
+    pub fn current_monitor(&self) -> Option<MonitorHandle> {
+        None
+    }
+}
+
+fn to_physical_size(size: Size) -> PhysicalSize<u32> {
+    size.to_physical(SCALE_FACTOR)
+}
+
+fn to_physical_position(position: Position) -> PhysicalPosition<i32> {
+    position.to_physical(SCALE_FACTOR)
+}
+
+/// Clamp a size between the size constraints of a window.
+fn clamp(
+    size: PhysicalSize<u32>,
+    min: Option<PhysicalSize<u32>>,
+    max: Option<PhysicalSize<u32>>,
+) -> PhysicalSize<u32> {
+    let mut size = size;
+    if let Some(min) = min {
+        size.width = size.width.max(min.width);
+        size.height = size.height.max(min.height);
+    }
+    if let Some(max) = max {
+        size.width = size.width.min(max.width);
+        size.height = size.height.min(max.height);
+    }
+    size
+}
+
+/// Run the headless event loop until the future completes.
+///
+/// Returns `None` if the event loop was asked to exit first.
+pub(crate) fn block_on<TS: ThreadSafety, F: Future>(
+    reactor: &Reactor<TS>,
+    future: F,
+) -> Option<F::Output> {
+    let (parker, unparker) = parking::pair();
+    let wakeup = move || {
+        unparker.unpark();
+    };
+
+    // The reactor keeps the first waker it is given, so reuse it for later event loops.
+    let notifier = match reactor.proxy() {
+        Some(notifier) => {
+            notifier.set_wakeup(wakeup);
+            notifier.clone()
+        }
+        None => {
+            let notifier = Arc::new(ReactorWaker::new(wakeup));
+            reactor.set_proxy(notifier.clone());
+            notifier
+        }
+    };
+    reactor.set_main_thread();
+
+    let waker = Waker::from(notifier.clone());
+    let mut cx = Context::from_waker(&waker);
+    futures_lite::pin!(future);
+
+    let mut dispatch: Option<Pin<Box<dyn Future<Output = ()> + '_>>> = None;
+    let mut wakers = vec![];
+
+    loop {
+        // Anything that happens from now on wakes up the parker.
+        notifier.clear_notified();
+
+        // Fire the timers that are due.
+        let deadline = reactor.process_timers(&mut wakers);
+        for waker in wakers.drain(..) {
+            waker.wake();
+        }
+
+        // Apply window operations from other tasks.
+        reactor.drain_headless_queue();
+
+        // Dispatch user events, one batch at a time.
+        if dispatch.is_none() {
+            dispatch = reactor
+                .post_event(Event::<()>::UserEvent(()), reactor.now())
+                .map(|dispatch| Box::pin(dispatch) as _);
+        }
+        if let Some(pending) = &mut dispatch {
+            if pending.as_mut().poll(&mut cx).is_ready() {
+                dispatch = None;
+            }
+        }
+
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+
+        if reactor.exit_requested().is_some() {
+            return None;
+        }
+
+        // End the current batch of input before going to sleep. There is nothing to dispatch.
+        drop(reactor.post_event(Event::<()>::AboutToWait, reactor.now()));
+
+        match deadline {
+            None => parker.park(),
+            Some(deadline) => {
+                parker.park_deadline(reactor.system_time(deadline));
+            }
+        }
+    }
+}
@@ -19,16 +19,26 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 #![doc = include_str!("../README.md")]
 
 // Private modules.
+mod clock;
 mod handler;
 mod oneoff;
 mod reactor;
 mod sync;
+#[cfg(feature = "macros")]
+mod test_harness;
 mod timer;
 
 // Modules we need to change for `async-winit`.
+#[cfg(all(feature = "automation", unix))]
+pub mod automation;
 pub mod event_loop;
 pub mod filter;
+#[cfg(feature = "headless")]
+pub mod headless;
 pub mod platform;
+#[cfg(feature = "record")]
+pub mod record;
+pub mod time;
 pub mod window;
 
 pub mod keyboard {
@@ -36,23 +46,45 @@ pub mod keyboard {
     pub use winit::keyboard::*;
 }
 
+/// Events and the data that they carry.
+///
+/// Most of these come from `winit`. [`KeyEvent`](event::KeyEvent) and
+/// [`InnerSizeWriter`](event::InnerSizeWriter) are defined by this crate instead, since `winit`
+/// doesn't allow creating them.
+///
+/// With the `serde` feature, the event structs defined by this crate implement `Serialize` and
+/// `Deserialize`. Device IDs are serialized as a `SerializedDeviceId`, which is a stable hash of
+/// the device ID. Key events are serialized without the `winit` event they were
+/// created from, and scale factor changes are serialized without their inner size writer.
 pub mod event {
     #[doc(inline)]
     pub use winit::event::*;
 
     pub use super::window::registration::{
-        AxisMotion, CursorMoved, KeyboardInput, MouseInput, MouseWheel, ScaleFactor,
-        ScaleFactorChanged, ScaleFactorChanging, TouchpadMagnify, TouchpadPressure, TouchpadRotate,
+        AxisMotion, CursorMoved, InnerSizeWriter, KeyEvent, KeyboardInput, MouseInput, MouseWheel,
+        ScaleFactor, ScaleFactorChanged, ScaleFactorChanging, TouchpadMagnify, TouchpadPressure,
+        TouchpadRotate,
     };
+
+    #[cfg(feature = "serde")]
+    pub use super::window::surrogates::SerializedDeviceId;
 }
 
 // Modules that can just be re-exported in `async-winit`.
 #[doc(inline)]
 pub use winit::{dpi, error, monitor};
 
-pub use handler::{Event, Handler, Waiter};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use handler::{Event, Handler, Timestamped, TimestampedWaiter, Waiter};
 pub use sync::{DefaultThreadSafety, ThreadSafety, ThreadUnsafe};
-pub use timer::Timer;
+pub use timer::{MissedTickBehavior, Timer};
+
+#[cfg(feature = "macros")]
+pub use async_winit_macros::test;
+
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use test_harness::run as __run_test;
 
 #[cfg(feature = "thread_safe")]
 pub use sync::ThreadSafe;
@@ -17,41 +17,148 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 */
 
 //! One-off channel, which handles completions of ongoing events.
-
-// TODO: This implementation uses a full channel, which allocates and is overall very inefficient.
-//       We should use a leaner implementation later.
+//!
+//! This is a single-slot channel: the sender and receiver share one reference-counted slot that
+//! holds the value and the waker of the receiving task. It avoids the allocations and boxed
+//! futures that a full channel would need.
 
 use crate::sync::{ThreadSafety, __private::*};
 
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// The event loop was closed before an operation could complete.
+///
+/// Most operations on windows and monitors are run by the event loop. If the event loop is
+/// destroyed while one of them is pending, the task that is waiting on it panics with this value as
+/// the payload. The panic can be recognized by catching it with [`std::panic::catch_unwind`] and
+/// calling [`downcast_ref`] on the payload.
+///
+/// [`downcast_ref`]: std::any::Any#method.downcast_ref
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopClosed;
+
+impl fmt::Display for LoopClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the event loop was closed before the operation completed")
+    }
+}
+
+impl std::error::Error for LoopClosed {}
+
+/// The slot shared between both ends of the channel.
+struct Slot<T> {
+    /// The value, if it has been sent.
+    value: Option<T>,
+
+    /// The waker of the task waiting on the value.
+    waker: Option<Waker>,
+
+    /// The sender was dropped without sending a value.
+    closed: bool,
+}
+
 /// A oneoff channel that can be used to receive a single event.
 pub(crate) struct Oneoff<T, TS: ThreadSafety> {
-    /// The channel used to receive the event.
-    rx: TS::Receiver<T>,
+    /// The slot to receive the value from.
+    slot: TS::Rc<TS::Mutex<Slot<T>>>,
 }
 
 impl<T, TS: ThreadSafety> Oneoff<T, TS> {
     /// Wait for the event to be sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a [`LoopClosed`] payload if the event loop was closed before the event was
+    /// sent.
     pub(crate) async fn recv(self) -> T {
-        self.rx.recv().await.unwrap()
+        match self.await {
+            Ok(value) => value,
+            Err(err) => std::panic::panic_any(err),
+        }
+    }
+}
+
+impl<T, TS: ThreadSafety> Future for Oneoff<T, TS> {
+    type Output = Result<T, LoopClosed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+
+        if let Some(value) = slot.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+
+        if slot.closed {
+            return Poll::Ready(Err(LoopClosed));
+        }
+
+        // Register our waker, avoiding a clone if it is the same one.
+        match &slot.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => slot.waker = Some(cx.waker().clone()),
+        }
+
+        Poll::Pending
     }
 }
 
 /// The sender end of the oneoff channel.
 pub(crate) struct Complete<T, TS: ThreadSafety> {
-    /// The channel used to send the event.
-    tx: TS::Sender<T>,
+    /// The slot to send the value into.
+    ///
+    /// This is `None` once the value has been sent.
+    slot: Option<TS::Rc<TS::Mutex<Slot<T>>>>,
 }
 
 impl<T, TS: ThreadSafety> Complete<T, TS> {
     /// Send the event.
-    pub(crate) fn send(self, event: T) {
-        self.tx.try_send(event).ok();
+    pub(crate) fn send(mut self, event: T) {
+        if let Some(slot) = self.slot.take() {
+            let waker = {
+                let mut slot = slot.lock().unwrap();
+                slot.value = Some(event);
+                slot.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T, TS: ThreadSafety> Drop for Complete<T, TS> {
+    fn drop(&mut self) {
+        // If we never sent anything, tell the receiver that it won't get anything.
+        if let Some(slot) = self.slot.take() {
+            let waker = {
+                let mut slot = slot.lock().unwrap();
+                slot.closed = true;
+                slot.waker.take()
+            };
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
     }
 }
 
 /// Create a pair of oneoff channels.
 pub(crate) fn oneoff<T, TS: ThreadSafety>() -> (Complete<T, TS>, Oneoff<T, TS>) {
-    let (tx, rx) = TS::channel_bounded(1);
+    let slot = TS::Rc::new(TS::Mutex::new(Slot {
+        value: None,
+        waker: None,
+        closed: false,
+    }));
 
-    (Complete { tx }, Oneoff { rx })
+    (
+        Complete {
+            slot: Some(slot.clone()),
+        },
+        Oneoff { slot },
+    )
 }
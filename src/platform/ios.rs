@@ -44,7 +44,10 @@ pub trait EventLoopExtIOS: sealed::EventLoopPrivate {
 impl<TS: ThreadSafety> EventLoopExtIOS for EventLoop<TS> {
     fn idiom(&self) -> Idiom {
         use winit::platform::ios::EventLoopExtIOS as _;
-        self.inner.idiom()
+        self.inner
+            .as_ref()
+            .expect("headless event loops don't have an idiom")
+            .idiom()
     }
 }
 
@@ -53,7 +53,18 @@ impl<TS: ThreadSafety> EventLoopExtRunOnDemand for EventLoop<TS> {
     {
         use winit::platform::run_on_demand::EventLoopExtRunOnDemand as _;
 
-        let inner = &mut self.inner;
+        let inner = match &mut self.inner {
+            Some(inner) => inner,
+            #[cfg(feature = "headless")]
+            None => {
+                return match crate::headless::block_on(&crate::reactor::Reactor::<TS>::get(), fut) {
+                    Some(output) => ReturnOrFinish::FutureReturned(output),
+                    None => ReturnOrFinish::Output(Ok(())),
+                };
+            }
+            #[cfg(not(feature = "headless"))]
+            None => unreachable!("headless event loops need the `headless` feature"),
+        };
 
         pin!(fut);
 
@@ -63,12 +74,12 @@ impl<TS: ThreadSafety> EventLoopExtRunOnDemand for EventLoop<TS> {
         let exit = inner.run_on_demand({
             let output = &mut output;
             move |event, elwt| match filter.handle_event(fut.as_mut(), event, elwt) {
-                ReturnOrFinish::FutureReturned(out) => {
+                ReturnOrFinish::FutureReturned((out, _)) => {
                     *output = Some(out);
                     elwt.exit();
                 }
 
-                ReturnOrFinish::Output(()) => {}
+                ReturnOrFinish::Output(_) => {}
             }
         });
 
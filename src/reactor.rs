@@ -18,31 +18,48 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 
 //! The shared reactor used by the runtime.
 
+use crate::clock::Clock;
+use crate::event_loop::InterceptAction;
 use crate::filter::ReactorWaker;
 use crate::handler::Handler;
-use crate::oneoff::Complete;
+#[cfg(feature = "headless")]
+use crate::headless::HeadlessWindow;
+use crate::oneoff::{oneoff, Complete};
 use crate::sync::{ThreadSafety, __private::*};
-use crate::window::registration::Registration as WinRegistration;
-use crate::window::WindowBuilder;
+use crate::window::registration::{KeyboardInput, Registration as WinRegistration};
+use crate::window::{RawWindow, WindowBuilder, WindowUpdate};
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::task::Waker;
+use std::thread::ThreadId;
 use std::time::{Duration, Instant};
 
 use winit::dpi::{PhysicalPosition, PhysicalSize, Position, Size};
 use winit::error::{ExternalError, NotSupportedError, OsError};
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
 use winit::monitor::MonitorHandle;
 use winit::window::{
     CursorGrabMode, CursorIcon, Fullscreen, Icon, ImePurpose, ResizeDirection, Theme,
-    UserAttentionType, Window, WindowId, WindowLevel,
+    UserAttentionType, WindowId, WindowLevel,
 };
 
+mod user_events;
+mod wheel;
+
+pub(crate) use user_events::Slot as UserEventSlot;
+use user_events::{PendingUserEvents, UserEvents};
+use wheel::TimerWheel;
+
 const NEEDS_EXIT: i64 = 0x1;
 const EXIT_CODE_SHIFT: u32 = 1;
 
+/// Whether a reactor currently exists.
+static REACTOR_EXISTS: AtomicBool = AtomicBool::new(false);
+
 #[doc(hidden)]
 pub struct Reactor<T: ThreadSafety> {
     /// The exit code to exit with, if any.
@@ -59,45 +76,117 @@ pub struct Reactor<T: ThreadSafety> {
     /// Used to wake up the event loop.
     proxy: T::OnceLock<Arc<ReactorWaker>>,
 
-    /// The timer wheel.
-    timers: T::Mutex<BTreeMap<(Instant, usize), Waker>>,
+    /// The clock used for timers, if it isn't the system clock.
+    clock: T::OnceLock<Arc<dyn Clock>>,
 
-    /// Queue of timer operations.
-    timer_op_queue: T::ConcurrentQueue<TimerOp>,
+    /// The ID of the thread that runs the event loop.
+    ///
+    /// Window operations called from this thread can be run directly instead of going through
+    /// `evl_ops`.
+    main_thread: T::OnceLock<ThreadId>,
+
+    /// The timer wheel.
+    timers: T::Mutex<TimerWheel>,
 
     /// The last timer ID we used.
     timer_id: T::AtomicUsize,
 
     /// Registration for event loop events.
     pub(crate) evl_registration: GlobalRegistration<T>,
+
+    /// Whether the application is suspended.
+    suspended: AtomicBool,
+
+    /// Time spent suspended, for timers that are paused while suspended.
+    suspension: T::Mutex<Suspension>,
+
+    /// User-defined events.
+    user_events: UserEvents<T>,
+
+    /// Window event interceptors, sorted by descending priority.
+    interceptors: T::Mutex<Vec<Interceptor<T>>>,
+
+    /// The last interceptor ID we used.
+    interceptor_id: T::AtomicUsize,
+
+    /// The window that currently has focus, if any.
+    focused_window: T::Mutex<Option<WindowId>>,
+
+    /// The recording that events are written to, if any.
+    #[cfg(feature = "record")]
+    recorder: T::Mutex<Option<crate::record::Sink>>,
+}
+
+/// A function that sees window events before they are dispatched.
+pub(crate) type InterceptFn =
+    Box<dyn FnMut(&WindowId, &mut WindowEvent) -> InterceptAction + Send + 'static>;
+
+/// A registered interceptor.
+struct Interceptor<T: ThreadSafety> {
+    /// The ID of the interceptor.
+    id: usize,
+
+    /// The priority of the interceptor.
+    priority: i32,
+
+    /// The function to call.
+    ///
+    /// This is reference counted so it can be called without holding the list locked.
+    f: T::Rc<T::Mutex<InterceptFn>>,
 }
 
-enum TimerOp {
-    /// Add a new timer.
-    InsertTimer(Instant, usize, Waker),
+/// Keeps track of the time that the application spent suspended.
+struct Suspension {
+    /// The time at which the application was suspended, if it is currently suspended.
+    since: Option<Instant>,
+
+    /// The total time spent suspended, not including the current suspension.
+    total: Duration,
 
-    /// Delete an existing timer.
-    RemoveTimer(Instant, usize),
+    /// The number of times the application was suspended.
+    count: u64,
+
+    /// Paused timers to wake up once the application is resumed.
+    wakers: Vec<Waker>,
+}
+
+impl<TS: ThreadSafety> Drop for Reactor<TS> {
+    fn drop(&mut self) {
+        REACTOR_EXISTS.store(false, Ordering::SeqCst);
+    }
 }
 
 impl<TS: ThreadSafety> Reactor<TS> {
     /// Create an empty reactor.
     pub(crate) fn new() -> Self {
         println!("Creating a new reactor");
-        static ALREADY_EXISTS: AtomicBool = AtomicBool::new(false);
-        if ALREADY_EXISTS.swap(true, Ordering::SeqCst) {
+        if REACTOR_EXISTS.swap(true, Ordering::SeqCst) {
             panic!("Only one instance of `Reactor` can exist at a time");
         }
 
         Reactor {
             exit_code: <TS::AtomicI64>::new(0),
             proxy: TS::OnceLock::new(),
+            clock: TS::OnceLock::new(),
+            main_thread: TS::OnceLock::new(),
             evl_ops: TS::channel_bounded(1024),
             windows: TS::Mutex::new(HashMap::new()),
-            timers: TS::Mutex::new(BTreeMap::new()),
-            timer_op_queue: TS::ConcurrentQueue::bounded(1024),
+            timers: TS::Mutex::new(TimerWheel::new(Instant::now())),
             timer_id: TS::AtomicUsize::new(1),
             evl_registration: GlobalRegistration::new(),
+            suspended: AtomicBool::new(false),
+            suspension: TS::Mutex::new(Suspension {
+                since: None,
+                total: Duration::ZERO,
+                count: 0,
+                wakers: Vec::new(),
+            }),
+            user_events: UserEvents::new(),
+            interceptors: TS::Mutex::new(Vec::new()),
+            interceptor_id: TS::AtomicUsize::new(1),
+            focused_window: TS::Mutex::new(None),
+            #[cfg(feature = "record")]
+            recorder: TS::Mutex::new(None),
         }
     }
 
@@ -106,9 +195,83 @@ impl<TS: ThreadSafety> Reactor<TS> {
         TS::get_reactor()
     }
 
+    /// Get the current time on the reactor's clock, without creating the reactor.
+    ///
+    /// This is the system time if there is no reactor.
+    pub(crate) fn now_if_exists() -> Instant {
+        match TS::try_get_reactor() {
+            Some(reactor) => reactor.now(),
+            None => Instant::now(),
+        }
+    }
+
     /// Set the event loop proxy.
     pub(crate) fn set_proxy(&self, proxy: Arc<ReactorWaker>) {
-        self.proxy.set(proxy).ok();
+        if let Some(clock) = self.clock.get() {
+            clock.register(&Waker::from(proxy.clone()));
+        }
+
+        self.proxy.set(proxy.clone()).ok();
+
+        // User events may have been sent before the event loop started.
+        if self.user_events.is_queued() {
+            proxy.send_wakeup();
+        }
+    }
+
+    /// Get the event loop proxy, if it has been set.
+    #[cfg(feature = "headless")]
+    pub(crate) fn proxy(&self) -> Option<&Arc<ReactorWaker>> {
+        self.proxy.get()
+    }
+
+    /// Set the clock used for timers.
+    ///
+    /// Only the first clock that is set is used.
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        if self.clock.get().is_some() {
+            return;
+        }
+
+        if let Some(proxy) = self.proxy.get() {
+            clock.register(&Waker::from(proxy.clone()));
+        }
+
+        // Start the timer wheel at the clock's current time.
+        let mut timers = self.timers.lock().unwrap();
+        if timers.is_empty() {
+            *timers = TimerWheel::new(clock.now());
+        }
+
+        self.clock.set(clock).ok();
+    }
+
+    /// Get the current time, according to the reactor's clock.
+    #[inline]
+    pub(crate) fn now(&self) -> Instant {
+        match self.clock.get() {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Convert a time on the reactor's clock to a time on the system clock.
+    pub(crate) fn system_time(&self, time: Instant) -> Instant {
+        match self.clock.get() {
+            Some(clock) => Instant::now() + time.saturating_duration_since(clock.now()),
+            None => time,
+        }
+    }
+
+    /// Mark the current thread as the one running the event loop.
+    pub(crate) fn set_main_thread(&self) {
+        self.main_thread.set(crate::sync::thread_id()).ok();
+    }
+
+    /// Tell whether we are currently on the thread running the event loop.
+    #[inline]
+    pub(crate) fn is_main_thread(&self) -> bool {
+        self.main_thread.get() == Some(&crate::sync::thread_id())
     }
 
     /// Get whether or not we need to exit, and the code as well.
@@ -133,18 +296,24 @@ impl<TS: ThreadSafety> Reactor<TS> {
     }
 
     /// Insert a new timer into the timer wheel.
-    pub(crate) fn insert_timer(&self, deadline: Instant, waker: &Waker) -> usize {
+    ///
+    /// The timer fires somewhere between `deadline` and `deadline + tolerance`. If `pausable` is
+    /// set, the timer is removed when the application is suspended and woken up once it resumes.
+    pub(crate) fn insert_timer(
+        &self,
+        deadline: Instant,
+        tolerance: Duration,
+        pausable: bool,
+        waker: &Waker,
+    ) -> usize {
         // Generate a new ID.
         let id = self.timer_id.fetch_add(1, Ordering::Relaxed);
 
         // Insert the timer into the timer wheel.
-        let mut op = TimerOp::InsertTimer(deadline, id, waker.clone());
-        while let Err(e) = self.timer_op_queue.push(op) {
-            // Process incoming timer operations.
-            let mut timers = self.timers.lock().unwrap();
-            self.process_timer_ops(&mut timers);
-            op = e;
-        }
+        self.timers
+            .lock()
+            .unwrap()
+            .insert(id, deadline, tolerance, pausable, waker.clone());
 
         // Notify that we have new timers.
         self.notify();
@@ -154,21 +323,22 @@ impl<TS: ThreadSafety> Reactor<TS> {
     }
 
     /// Remove a timer from the timer wheel.
-    pub(crate) fn remove_timer(&self, deadline: Instant, id: usize) {
-        let mut op = TimerOp::RemoveTimer(deadline, id);
-        while let Err(e) = self.timer_op_queue.push(op) {
-            // Process incoming timer operations.
-            let mut timers = self.timers.lock().unwrap();
-            self.process_timer_ops(&mut timers);
-            op = e;
+    pub(crate) fn remove_timer(&self, id: usize) {
+        let waker = self.timers.lock().unwrap().remove(id);
+
+        if let Some(waker) = waker {
+            // Don't let a waker that panics on drop blow everything up.
+            std::panic::catch_unwind(|| drop(waker)).ok();
         }
     }
 
     /// Insert a window into the window list.
-    pub(crate) fn insert_window(&self, id: WindowId) -> TS::Rc<WinRegistration<TS>> {
+    pub(crate) fn insert_window(&self, window: &TS::Rc<RawWindow>) -> TS::Rc<WinRegistration<TS>> {
+        let id = window.id();
         println!("Insert window {:?}", id);
         let mut windows = self.windows.lock().unwrap();
         let registration = TS::Rc::new(WinRegistration::new());
+        registration.set_window(window);
         windows.insert(id, registration.clone());
         registration
     }
@@ -178,55 +348,107 @@ impl<TS: ThreadSafety> Reactor<TS> {
         println!("Removing a window {:?}", id);
         let mut windows = self.windows.lock().unwrap();
         windows.remove(&id);
+
+        let mut focused_window = self.focused_window.lock().unwrap();
+        if *focused_window == Some(id) {
+            *focused_window = None;
+        }
     }
 
-    /// Process pending timer operations.
-    fn process_timer_ops(&self, timers: &mut BTreeMap<(Instant, usize), Waker>) {
-        // Limit the number of operations we process at once to avoid starving other tasks.
-        let limit = self.timer_op_queue.capacity();
-
-        self.timer_op_queue
-            .try_iter()
-            .take(limit)
-            .for_each(|op| match op {
-                TimerOp::InsertTimer(deadline, id, waker) => {
-                    timers.insert((deadline, id), waker);
-                }
-                TimerOp::RemoveTimer(deadline, id) => {
-                    if let Some(waker) = timers.remove(&(deadline, id)) {
-                        // Don't let a waker that panics on drop blow everything up.
-                        std::panic::catch_unwind(|| drop(waker)).ok();
-                    }
-                }
-            });
+    /// Get every window in the window list.
+    #[cfg(all(feature = "automation", unix))]
+    pub(crate) fn windows(&self) -> Vec<(WindowId, TS::Rc<WinRegistration<TS>>)> {
+        self.windows
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, registration)| (*id, registration.clone()))
+            .collect()
     }
 
-    /// Process timers and return the amount of time to wait.
-    pub(crate) fn process_timers(&self, wakers: &mut Vec<Waker>) -> Option<Instant> {
-        // Process incoming timer operations.
-        let mut timers = self.timers.lock().unwrap();
-        self.process_timer_ops(&mut timers);
+    /// Get the window that currently has focus, if any.
+    pub(crate) fn focused_window(&self) -> Option<WindowId> {
+        *self.focused_window.lock().unwrap()
+    }
+
+    /// Tell whether the application is suspended.
+    pub(crate) fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::Acquire)
+    }
+
+    /// Get the total time that the application has spent suspended, along with the number of
+    /// times it was suspended.
+    ///
+    /// The time does not include the current suspension, if the application is suspended.
+    pub(crate) fn suspended_time(&self) -> (Duration, u64) {
+        let suspension = self.suspension.lock().unwrap();
+        (suspension.total, suspension.count)
+    }
+
+    /// Wake up a paused timer once the application is resumed.
+    pub(crate) fn wake_on_resume(&self, waker: &Waker) {
+        let mut suspension = self.suspension.lock().unwrap();
+
+        if suspension.since.is_none() {
+            // We were resumed in the meantime.
+            drop(suspension);
+            waker.wake_by_ref();
+        } else if !suspension.wakers.iter().any(|w| w.will_wake(waker)) {
+            suspension.wakers.push(waker.clone());
+        }
+    }
+
+    /// Mark the application as suspended or resumed.
+    fn set_suspended(&self, suspended: bool) {
+        if self.is_suspended() == suspended {
+            return;
+        }
 
-        let now = Instant::now();
+        let now = self.now();
+        if suspended {
+            self.suspended.store(true, Ordering::Release);
 
-        // Split timers into pending and ready timers.
-        let pending = timers.split_off(&(now + Duration::from_nanos(1), 0));
-        let ready = std::mem::replace(&mut *timers, pending);
+            let mut suspension = self.suspension.lock().unwrap();
+            suspension.since = Some(now);
+            suspension.count += 1;
 
-        // Figure out how long it will be until the next timer is ready.
-        let deadline = if ready.is_empty() {
-            timers.keys().next().map(|(deadline, _)| *deadline)
+            // Paused timers shouldn't wake up the event loop while we're suspended.
+            let mut timers = self.timers.lock().unwrap();
+            timers.take_pausable(&mut suspension.wakers);
         } else {
-            // There are timers ready to fire now.
-            Some(now)
-        };
+            let wakers = {
+                let mut suspension = self.suspension.lock().unwrap();
+                if let Some(since) = suspension.since.take() {
+                    suspension.total += now.saturating_duration_since(since);
+                }
+                std::mem::take(&mut suspension.wakers)
+            };
 
-        drop(timers);
+            // Only mark us as resumed once paused timers can see how long we were suspended.
+            self.suspended.store(false, Ordering::Release);
 
-        // Push wakers for ready timers.
-        wakers.extend(ready.into_values());
+            // Let paused timers reschedule themselves.
+            for waker in wakers {
+                waker.wake();
+            }
+        }
 
-        deadline
+        // This changes the visibility of every window.
+        let windows = self
+            .windows
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        for registration in windows {
+            registration.visibility_changed();
+        }
+    }
+
+    /// Process timers and return the time at which the next timer fires.
+    pub(crate) fn process_timers(&self, wakers: &mut Vec<Waker>) -> Option<Instant> {
+        self.timers.lock().unwrap().process(self.now(), wakers)
     }
 
     /// Wake up the event loop.
@@ -236,6 +458,74 @@ impl<TS: ThreadSafety> Reactor<TS> {
         }
     }
 
+    /// Get the slot for a type of user event.
+    pub(crate) fn user_event_slot<T: Clone + Send + 'static>(&self) -> &UserEventSlot<T, TS> {
+        self.user_events.slot()
+    }
+
+    /// Send a user event to be dispatched by the event loop.
+    pub(crate) fn send_user_event<T: Clone + Send + 'static>(&self, event: T) {
+        if self.user_events.push(event, self.now()) {
+            // Always send a wakeup, even if the loop is awake, so the events are dispatched.
+            if let Some(proxy) = self.proxy.get() {
+                proxy.send_wakeup();
+            }
+        }
+    }
+
+    /// Add an interceptor for window events.
+    ///
+    /// Returns the ID of the interceptor.
+    pub(crate) fn insert_interceptor(&self, priority: i32, f: InterceptFn) -> usize {
+        let id = self.interceptor_id.fetch_add(1, Ordering::Relaxed);
+        let mut interceptors = self.interceptors.lock().unwrap();
+
+        // Interceptors with the same priority run in the order they were added.
+        let index = interceptors.partition_point(|other| other.priority >= priority);
+        interceptors.insert(
+            index,
+            Interceptor {
+                id,
+                priority,
+                f: TS::Rc::new(TS::Mutex::new(f)),
+            },
+        );
+
+        id
+    }
+
+    /// Remove an interceptor for window events.
+    pub(crate) fn remove_interceptor(&self, id: usize) {
+        self.interceptors
+            .lock()
+            .unwrap()
+            .retain(|interceptor| interceptor.id != id);
+    }
+
+    /// Run the interceptors on a window event.
+    ///
+    /// Returns `false` if the event should be dropped.
+    fn intercept(&self, window_id: WindowId, event: &mut WindowEvent) -> bool {
+        let interceptors = self
+            .interceptors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|interceptor| interceptor.f.clone())
+            .collect::<Vec<_>>();
+
+        for f in interceptors {
+            let action = (f.lock().unwrap())(&window_id, event);
+            match action {
+                InterceptAction::Continue => {}
+                InterceptAction::Drop => return false,
+                InterceptAction::Replace(replacement) => *event = replacement,
+            }
+        }
+
+        true
+    }
+
     /// Push an event loop operation.
     pub(crate) async fn push_event_loop_op(&self, op: EventLoopOp<TS>) {
         if self.evl_ops.0.send(op).await.is_err() {
@@ -260,33 +550,247 @@ impl<TS: ThreadSafety> Reactor<TS> {
         }
     }
 
+    /// Drain the event loop operation queue, applying the operations to headless windows.
+    #[cfg(feature = "headless")]
+    pub(crate) fn drain_headless_queue(&self) {
+        for _ in 0..self.evl_ops.1.capacity() {
+            if let Some(op) = self.evl_ops.1.try_recv() {
+                op.run_headless();
+            } else {
+                break;
+            }
+        }
+    }
+
     pub fn evl_ops_len(&self) -> usize {
         self.evl_ops.1.len()
     }
 
     /// Post an event to the reactor.
-    pub(crate) async fn post_event<T: 'static>(&self, event: winit::event::Event<T>) {
+    ///
+    /// Returns `None` if nothing is listening for the event, in which case there is nothing to
+    /// dispatch.
+    pub(crate) fn post_event<T: 'static>(
+        &self,
+        event: winit::event::Event<T>,
+        timestamp: Instant,
+    ) -> Option<impl Future<Output = ()> + '_> {
+        #[cfg(feature = "record")]
+        if let Some(recorder) = &mut *self.recorder.lock().unwrap() {
+            recorder.record(&event, timestamp);
+        }
+
+        let dispatch = self.prepare(event)?;
+        Some(self.dispatch(dispatch, timestamp))
+    }
+
+    /// Start writing events to a recording.
+    ///
+    /// Returns `false` if a recording is already in progress.
+    #[cfg(feature = "record")]
+    pub(crate) fn start_recording(&self, sink: crate::record::Sink) -> bool {
+        let mut recorder = self.recorder.lock().unwrap();
+        if recorder.is_some() {
+            return false;
+        }
+
+        *recorder = Some(sink);
+        true
+    }
+
+    /// Stop writing events to the current recording.
+    #[cfg(feature = "record")]
+    pub(crate) fn stop_recording(&self) -> Option<crate::record::Sink> {
+        self.recorder.lock().unwrap().take()
+    }
+
+    /// Dispatch a synthetic event, as if it came from the event loop.
+    ///
+    /// The reactor's state is updated on the event loop thread, while the event is dispatched by
+    /// the calling task.
+    pub(crate) async fn inject(&self, event: winit::event::Event<()>) {
+        let dispatch = if self.is_main_thread() {
+            self.prepare(event)
+        } else {
+            let (tx, rx) = oneoff();
+            self.push_event_loop_op(EventLoopOp::Inject {
+                event: Box::new(event),
+                waker: tx,
+            })
+            .await;
+
+            rx.recv().await
+        };
+
+        if let Some(dispatch) = dispatch {
+            self.dispatch(dispatch, self.now()).await;
+        }
+    }
+
+    /// Get the registration of a window.
+    pub(crate) fn window_registration(
+        &self,
+        window_id: WindowId,
+    ) -> Option<TS::Rc<WinRegistration<TS>>> {
+        self.windows.lock().unwrap().get(&window_id).cloned()
+    }
+
+    /// Dispatch keyboard input that didn't come from the event loop to a window's handlers.
+    pub(crate) async fn inject_keyboard_input(
+        &self,
+        window_id: WindowId,
+        registration: &WinRegistration<TS>,
+        input: KeyboardInput,
+    ) {
+        let timestamp = self.now();
+        let any = if self.evl_registration.any_keyboard_input.has_listeners() {
+            Some(AnyWindowEvent::KeyboardInput(
+                window_id,
+                Box::new(input.clone()),
+            ))
+        } else {
+            None
+        };
+
+        registration.signal_keyboard_input(input, timestamp).await;
+        if let Some(any) = any {
+            self.evl_registration.signal_any(any, timestamp).await;
+        }
+    }
+
+    /// Update the reactor's state for an event.
+    ///
+    /// Returns `None` if nothing is listening for the event.
+    fn prepare<T: 'static>(&self, event: winit::event::Event<T>) -> Option<Dispatch<TS>> {
         use winit::event::Event;
 
-        match event {
-            Event::WindowEvent { window_id, event } => {
-                let registration = {
-                    let windows = self.windows.lock().unwrap();
-                    windows.get(&window_id).cloned()
-                };
-                if let Some(registration) = registration {
-                    registration.signal(event).await;
+        let dispatch = match event {
+            Event::WindowEvent {
+                window_id,
+                mut event,
+            } => {
+                let registration = self.window_registration(window_id)?;
+                if !self.intercept(window_id, &mut event) {
+                    return None;
                 }
+                if let WindowEvent::Focused(focused) = event {
+                    let mut focused_window = self.focused_window.lock().unwrap();
+                    if focused {
+                        *focused_window = Some(window_id);
+                    } else if *focused_window == Some(window_id) {
+                        *focused_window = None;
+                    }
+                }
+                registration.update_visibility(&event);
+                registration.push_batched(&event);
+                let any = self.evl_registration.any_event(window_id, &event);
+                if !registration.is_listening(&event) && any.is_none() {
+                    return None;
+                }
+                Dispatch::Window(registration, event, any)
             }
             Event::Resumed => {
-                self.evl_registration.resumed.run_with(&mut ()).await;
+                self.set_suspended(false);
+                if !self.evl_registration.resumed.has_listeners() {
+                    return None;
+                }
+                Dispatch::Resumed
             }
-            Event::Suspended => self.evl_registration.suspended.run_with(&mut ()).await,
-            _ => {}
+            Event::Suspended => {
+                self.set_suspended(true);
+                if !self.evl_registration.suspended.has_listeners() {
+                    return None;
+                }
+                Dispatch::Suspended
+            }
+            Event::DeviceEvent { device_id, event } => {
+                if !self.evl_registration.device_event.has_listeners() {
+                    return None;
+                }
+                Dispatch::Device(device_id, event)
+            }
+            Event::UserEvent(_) => {
+                let events = self.user_events.take();
+                if events.is_empty() {
+                    return None;
+                }
+                Dispatch::User(events)
+            }
+            Event::AboutToWait => {
+                // This ends the current batch of input for every window.
+                let windows = self
+                    .windows
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for registration in windows {
+                    registration.flush_batches();
+                }
+
+                return None;
+            }
+            _ => return None,
+        };
+
+        Some(dispatch)
+    }
+
+    /// Dispatch an event to its handlers.
+    async fn dispatch(&self, dispatch: Dispatch<TS>, timestamp: Instant) {
+        match dispatch {
+            Dispatch::Window(registration, event, any) => {
+                registration.signal(event, timestamp).await;
+                if let Some(any) = any {
+                    self.evl_registration.signal_any(any, timestamp).await;
+                }
+            }
+            Dispatch::Resumed => {
+                self.evl_registration
+                    .resumed
+                    .run_with(&mut (), timestamp)
+                    .await
+            }
+            Dispatch::Suspended => {
+                self.evl_registration
+                    .suspended
+                    .run_with(&mut (), timestamp)
+                    .await
+            }
+            Dispatch::Device(device_id, event) => {
+                self.evl_registration
+                    .device_event
+                    .run_with(&mut (device_id, event), timestamp)
+                    .await
+            }
+            Dispatch::User(events) => self.user_events.dispatch(events).await,
         }
     }
 }
 
+/// An event that has listeners and needs to be dispatched.
+pub(crate) enum Dispatch<TS: ThreadSafety> {
+    /// An event for a window, along with the event for the aggregated handlers, if any.
+    Window(
+        TS::Rc<WinRegistration<TS>>,
+        winit::event::WindowEvent,
+        Option<AnyWindowEvent>,
+    ),
+
+    /// The application was resumed.
+    Resumed,
+
+    /// The application was suspended.
+    Suspended,
+
+    /// An event from a device.
+    Device(DeviceId, DeviceEvent),
+
+    /// User events were sent.
+    User(PendingUserEvents),
+}
+
 /// An operation to run in the main event loop thread.
 pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Build a window.
@@ -295,7 +799,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
         builder: Box<WindowBuilder>,
 
         /// The window has been built.
-        waker: Complete<Result<winit::window::Window, OsError>, TS>,
+        waker: Complete<Result<RawWindow, OsError>, TS>,
     },
 
     /// Get the primary monitor.
@@ -307,7 +811,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the inner position of the window.
     InnerPosition {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Result<PhysicalPosition<i32>, NotSupportedError>, TS>,
@@ -316,7 +820,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the outer position of the window.
     OuterPosition {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Result<PhysicalPosition<i32>, NotSupportedError>, TS>,
@@ -325,7 +829,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the outer position.
     SetOuterPosition {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The position.
         position: Position,
@@ -337,7 +841,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the inner size.
     InnerSize {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<PhysicalSize<u32>, TS>,
@@ -346,7 +850,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the min inner size.
     SetMinInnerSize {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The size.
         size: Option<Size>,
@@ -358,7 +862,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the max inner size.
     SetMaxInnerSize {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The size.
         size: Option<Size>,
@@ -370,7 +874,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the outer size.
     OuterSize {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<PhysicalSize<u32>, TS>,
@@ -379,7 +883,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the resize increments.
     ResizeIncrements {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<PhysicalSize<u32>>, TS>,
@@ -388,7 +892,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the resize increments.
     SetResizeIncrements {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The size.
         size: Option<Size>,
@@ -400,7 +904,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the title.
     SetTitle {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The title.
         title: String,
@@ -412,7 +916,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is transparent.
     SetTransparent {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is transparent.
         transparent: bool,
@@ -424,7 +928,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether or not the window is resizable.
     SetResizable {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether or not the window is resizable.
         resizable: bool,
@@ -436,7 +940,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is visible.
     SetVisible {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is visible.
         visible: bool,
@@ -448,7 +952,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is resizable.
     Resizable {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<bool, TS>,
@@ -457,7 +961,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is visible.
     Visible {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<bool>, TS>,
@@ -466,7 +970,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is minimized.
     SetMinimized {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is minimized.
         minimized: bool,
@@ -478,7 +982,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is minimized.
     Minimized {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<bool>, TS>,
@@ -487,7 +991,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is maximized.
     SetMaximized {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is maximized.
         maximized: bool,
@@ -499,7 +1003,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is maximized.
     Maximized {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<bool, TS>,
@@ -508,7 +1012,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is fullscreen.
     SetFullscreen {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is fullscreen.
         fullscreen: Option<Fullscreen>,
@@ -520,7 +1024,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is fullscreen.
     Fullscreen {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<Fullscreen>, TS>,
@@ -529,7 +1033,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the window is decorated.
     SetDecorated {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the window is decorated.
         decorated: bool,
@@ -541,7 +1045,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get whether the window is decorated.
     Decorated {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<bool, TS>,
@@ -550,7 +1054,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the window level.
     SetWindowLevel {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The window level.
         level: WindowLevel,
@@ -562,7 +1066,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the window icon.
     SetWindowIcon {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The window icon.
         icon: Option<Icon>,
@@ -574,7 +1078,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the IME position.
     SetImeCursorArea {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The IME position.
         position: Position,
@@ -589,7 +1093,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether IME is allowed.
     SetImeAllowed {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether IME is allowed.
         allowed: bool,
@@ -601,7 +1105,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the IME purpose.
     SetImePurpose {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The IME purpose.
         purpose: ImePurpose,
@@ -613,7 +1117,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Focus the window.
     FocusWindow {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<(), TS>,
@@ -622,7 +1126,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Tell whether or not the window is focused.
     Focused {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<bool, TS>,
@@ -631,7 +1135,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Request user attention.
     RequestUserAttention {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The request.
         request_type: Option<UserAttentionType>,
@@ -643,7 +1147,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the theme of the window.
     SetTheme {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The theme.
         theme: Option<Theme>,
@@ -655,7 +1159,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the theme of the window.
     Theme {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<Theme>, TS>,
@@ -664,7 +1168,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the content is protected.
     SetProtectedContent {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the content is protected.
         protected: bool,
@@ -676,7 +1180,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the title.
     Title {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<String, TS>,
@@ -685,7 +1189,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the cursor icon.
     SetCursorIcon {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The cursor icon.
         icon: CursorIcon,
@@ -697,7 +1201,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the cursor position.
     SetCursorPosition {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The cursor position.
         position: Position,
@@ -709,7 +1213,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the cursor grab.
     SetCursorGrab {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The mode to grab the cursor.
         mode: CursorGrabMode,
@@ -721,7 +1225,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set whether the cursor is visible.
     SetCursorVisible {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Whether the cursor is visible.
         visible: bool,
@@ -733,7 +1237,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Drag the window.
     DragWindow {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Result<(), ExternalError>, TS>,
@@ -742,7 +1246,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Drag-resize the window.
     DragResizeWindow {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         direction: ResizeDirection,
 
@@ -753,7 +1257,7 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Set the cursor hit test.
     SetCursorHitTest {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// The cursor hit test.
         hit_test: bool,
@@ -765,11 +1269,32 @@ pub(crate) enum EventLoopOp<TS: ThreadSafety> {
     /// Get the current monitor.
     CurrentMonitor {
         /// The window.
-        window: TS::Rc<Window>,
+        window: TS::Rc<RawWindow>,
 
         /// Wake up the task.
         waker: Complete<Option<MonitorHandle>, TS>,
     },
+
+    /// Apply a batch of changes to the window.
+    Update {
+        /// The window.
+        window: TS::Rc<RawWindow>,
+
+        /// The changes to apply.
+        update: Box<WindowUpdate>,
+
+        /// Wake up the task.
+        waker: Complete<(), TS>,
+    },
+
+    /// Prepare a synthetic event for dispatch.
+    Inject {
+        /// The event.
+        event: Box<winit::event::Event<()>>,
+
+        /// Wake up the task with the event to dispatch, if any.
+        waker: Complete<Option<Dispatch<TS>>, TS>,
+    },
 }
 
 impl<TS: ThreadSafety> fmt::Debug for EventLoopOp<TS> {
@@ -833,7 +1358,12 @@ impl<TS: ThreadSafety> EventLoopOp<TS> {
     fn run<T: 'static>(self, target: &winit::event_loop::EventLoopWindowTarget<T>) {
         match self {
             EventLoopOp::BuildWindow { builder, waker } => {
-                waker.send(builder.into_winit_builder().build(target));
+                waker.send(
+                    builder
+                        .into_winit_builder()
+                        .build(target)
+                        .map(RawWindow::from),
+                );
             }
 
             EventLoopOp::PrimaryMonitor(waker) => {
@@ -844,6 +1374,41 @@ impl<TS: ThreadSafety> EventLoopOp<TS> {
                 waker.send(target.available_monitors().collect());
             }
 
+            op => op.run_on_window(),
+        }
+    }
+
+    /// Run this event loop operation without a display.
+    #[cfg(feature = "headless")]
+    fn run_headless(self) {
+        match self {
+            EventLoopOp::BuildWindow { builder, waker } => {
+                waker.send(Ok(RawWindow::from(HeadlessWindow::new(
+                    builder.attributes(),
+                ))));
+            }
+
+            EventLoopOp::PrimaryMonitor(waker) => {
+                waker.send(None);
+            }
+
+            EventLoopOp::AvailableMonitors(waker) => {
+                waker.send(vec![]);
+            }
+
+            op => op.run_on_window(),
+        }
+    }
+
+    /// Run an operation that doesn't need the window target.
+    fn run_on_window(self) {
+        match self {
+            EventLoopOp::BuildWindow { .. }
+            | EventLoopOp::PrimaryMonitor(_)
+            | EventLoopOp::AvailableMonitors(_) => {
+                unreachable!("operation needs the window target")
+            }
+
             EventLoopOp::InnerPosition { window, waker } => {
                 waker.send(window.inner_position());
             }
@@ -1114,6 +1679,19 @@ impl<TS: ThreadSafety> EventLoopOp<TS> {
                 waker.send(window.current_monitor());
             }
 
+            EventLoopOp::Update {
+                window,
+                update,
+                waker,
+            } => {
+                update.apply(&window);
+                waker.send(());
+            }
+
+            EventLoopOp::Inject { event, waker } => {
+                waker.send(Reactor::<TS>::get().prepare(*event));
+            }
+
             EventLoopOp::SetTransparent {
                 window,
                 transparent,
@@ -1150,6 +1728,25 @@ impl<TS: ThreadSafety> EventLoopOp<TS> {
 pub(crate) struct GlobalRegistration<T: ThreadSafety> {
     pub(crate) resumed: Handler<(), T>,
     pub(crate) suspended: Handler<(), T>,
+
+    /// `DeviceEvent` for any device.
+    pub(crate) device_event: Handler<(DeviceId, DeviceEvent), T>,
+
+    /// `CloseRequested` for any window.
+    pub(crate) any_close_requested: Handler<WindowId, T>,
+
+    /// `Focused` for any window.
+    pub(crate) any_focused: Handler<(WindowId, bool), T>,
+
+    /// `KeyboardInput` for any window.
+    pub(crate) any_keyboard_input: Handler<(WindowId, KeyboardInput), T>,
+}
+
+/// A window event for the aggregated handlers.
+pub(crate) enum AnyWindowEvent {
+    CloseRequested(WindowId),
+    Focused(WindowId, bool),
+    KeyboardInput(WindowId, Box<KeyboardInput>),
 }
 
 impl<TS: ThreadSafety> GlobalRegistration<TS> {
@@ -1157,6 +1754,56 @@ impl<TS: ThreadSafety> GlobalRegistration<TS> {
         Self {
             resumed: Handler::new(),
             suspended: Handler::new(),
+            device_event: Handler::new(),
+            any_close_requested: Handler::new(),
+            any_focused: Handler::new(),
+            any_keyboard_input: Handler::new(),
+        }
+    }
+
+    /// Get the event for the aggregated handlers, if anything is listening for it.
+    fn any_event(&self, window_id: WindowId, event: &WindowEvent) -> Option<AnyWindowEvent> {
+        match event {
+            WindowEvent::CloseRequested if self.any_close_requested.has_listeners() => {
+                Some(AnyWindowEvent::CloseRequested(window_id))
+            }
+            WindowEvent::Focused(focused) if self.any_focused.has_listeners() => {
+                Some(AnyWindowEvent::Focused(window_id, *focused))
+            }
+            WindowEvent::KeyboardInput {
+                device_id,
+                event,
+                is_synthetic,
+            } if self.any_keyboard_input.has_listeners() => Some(AnyWindowEvent::KeyboardInput(
+                window_id,
+                Box::new(KeyboardInput {
+                    device_id: *device_id,
+                    event: event.clone().into(),
+                    is_synthetic: *is_synthetic,
+                }),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Dispatch an event to the aggregated handlers.
+    async fn signal_any(&self, event: AnyWindowEvent, timestamp: Instant) {
+        match event {
+            AnyWindowEvent::CloseRequested(mut window_id) => {
+                self.any_close_requested
+                    .run_with(&mut window_id, timestamp)
+                    .await
+            }
+            AnyWindowEvent::Focused(window_id, focused) => {
+                self.any_focused
+                    .run_with(&mut (window_id, focused), timestamp)
+                    .await
+            }
+            AnyWindowEvent::KeyboardInput(window_id, input) => {
+                self.any_keyboard_input
+                    .run_with(&mut (window_id, *input), timestamp)
+                    .await
+            }
         }
     }
 }
@@ -0,0 +1,214 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Queues for user-defined events.
+
+use crate::handler::Handler;
+use crate::sync::{ThreadSafety, __private::*};
+
+use std::any::{Any, TypeId};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+/// The user events that have been sent to the reactor.
+pub(crate) struct UserEvents<TS: ThreadSafety> {
+    /// The first slot in the list of slots, with one slot for each type of user event.
+    ///
+    /// Slots are only added to the end of the list, so references to them last as long as the
+    /// reactor.
+    head: TS::OnceLock<Box<SlotNode<TS>>>,
+
+    /// The types of the events waiting to be dispatched, in the order that they were sent.
+    ///
+    /// Each entry refers to the slot holding the event.
+    pending: TS::Mutex<VecDeque<TypeId>>,
+
+    /// Whether the event loop has been woken up for the pending events.
+    queued: AtomicBool,
+}
+
+/// The handler and queue for one type of user event.
+pub(crate) struct Slot<T: Clone + 'static, TS: ThreadSafety> {
+    /// The handler that the events are dispatched to.
+    pub(crate) handler: Handler<T, TS>,
+
+    /// The events that haven't been dispatched yet, along with the time that they were sent.
+    queue: TS::Mutex<VecDeque<(T, Instant)>>,
+}
+
+/// A slot in the list of slots.
+struct SlotNode<TS: ThreadSafety, S: ?Sized = dyn AnySlot<TS>> {
+    /// The type of event that the slot is for.
+    type_id: TypeId,
+
+    /// The next slot in the list.
+    next: TS::OnceLock<Box<SlotNode<TS>>>,
+
+    /// The `Slot<T, TS>` for the event type `T`.
+    slot: S,
+}
+
+// SAFETY: `UserEvents::slot` only creates slots for `Send` event types, and slots for those are
+// `Send + Sync` when using thread-safe primitives.
+#[cfg(feature = "thread_safe")]
+unsafe impl Send for SlotNode<crate::sync::ThreadSafe> {}
+#[cfg(feature = "thread_safe")]
+unsafe impl Sync for SlotNode<crate::sync::ThreadSafe> {}
+
+#[cfg(feature = "thread_safe")]
+#[allow(dead_code)]
+fn _assert_slots_are_thread_safe<T: Clone + Send + 'static>() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Slot<T, crate::sync::ThreadSafe>>();
+}
+
+/// The type-erased interface to a slot.
+trait AnySlot<TS: ThreadSafety> {
+    /// Get the slot as an `Any`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Dispatch the oldest event in this slot.
+    fn dispatch_one(&self) -> Pin<Box<dyn Future<Output = ()> + '_>>;
+}
+
+impl<T: Clone + 'static, TS: ThreadSafety> AnySlot<TS> for Slot<T, TS> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dispatch_one(&self) -> Pin<Box<dyn Future<Output = ()> + '_>> {
+        Box::pin(async move {
+            let next = self.queue.lock().unwrap().pop_front();
+            if let Some((mut event, timestamp)) = next {
+                self.handler.run_with(&mut event, timestamp).await;
+            }
+        })
+    }
+}
+
+impl<TS: ThreadSafety> UserEvents<TS> {
+    pub(crate) fn new() -> Self {
+        Self {
+            head: TS::OnceLock::new(),
+            pending: TS::Mutex::new(VecDeque::new()),
+            queued: AtomicBool::new(false),
+        }
+    }
+
+    /// Get the slot for a type of event, if it has been added.
+    fn get(&self, type_id: TypeId) -> Option<&dyn AnySlot<TS>> {
+        let mut next = self.head.get();
+        while let Some(node) = next {
+            if node.type_id == type_id {
+                return Some(&node.slot);
+            }
+            next = node.next.get();
+        }
+
+        None
+    }
+
+    /// Get the slot for a type of user event, adding it to the end of the list if it doesn't
+    /// exist yet.
+    pub(crate) fn slot<T: Clone + Send + 'static>(&self) -> &Slot<T, TS> {
+        let mut new: Option<Box<SlotNode<TS>>> = None;
+        let mut link = &self.head;
+
+        let node = loop {
+            match link.get() {
+                Some(node) if node.type_id == TypeId::of::<T>() => break node,
+                Some(node) => link = &node.next,
+                None => {
+                    let node = new.take().unwrap_or_else(|| {
+                        Box::new(SlotNode {
+                            type_id: TypeId::of::<T>(),
+                            next: TS::OnceLock::new(),
+                            slot: Slot::<T, TS> {
+                                handler: Handler::new(),
+                                queue: TS::Mutex::new(VecDeque::new()),
+                            },
+                        })
+                    });
+
+                    // If another thread added a slot first, look at it and try again.
+                    if let Err(node) = link.set(node) {
+                        new = Some(node);
+                    }
+                }
+            }
+        };
+
+        node.slot
+            .as_any()
+            .downcast_ref()
+            .expect("user event slot has the wrong type")
+    }
+
+    /// Queue an event to be dispatched.
+    ///
+    /// Returns `true` if the event loop needs to be woken up.
+    pub(crate) fn push<T: Clone + Send + 'static>(&self, event: T, timestamp: Instant) -> bool {
+        let slot = self.slot::<T>();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            slot.queue.lock().unwrap().push_back((event, timestamp));
+            pending.push_back(TypeId::of::<T>());
+        }
+
+        !self.queued.swap(true, Ordering::SeqCst)
+    }
+
+    /// Whether there are events that haven't been taken yet.
+    pub(crate) fn is_queued(&self) -> bool {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Take the events that are waiting to be dispatched.
+    pub(crate) fn take(&self) -> PendingUserEvents {
+        self.queued.store(false, Ordering::SeqCst);
+        PendingUserEvents {
+            types: mem::take(&mut *self.pending.lock().unwrap()),
+        }
+    }
+
+    /// Dispatch events that were taken from the queue, in order.
+    pub(crate) async fn dispatch(&self, events: PendingUserEvents) {
+        for type_id in events.types {
+            if let Some(slot) = self.get(type_id) {
+                slot.dispatch_one().await;
+            }
+        }
+    }
+}
+
+/// User events that have been taken from the queue.
+pub(crate) struct PendingUserEvents {
+    /// The types of the events, in the order that they were sent.
+    types: VecDeque<TypeId>,
+}
+
+impl PendingUserEvents {
+    /// Whether there are no events.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
@@ -0,0 +1,308 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! A hierarchical timer wheel.
+//!
+//! Timers are stored in six levels of 64 slots each. A slot on level `n` covers `64^n`
+//! milliseconds, so the wheel spans a little over two years. Timers that are further in the future
+//! wait at the end of the wheel, and are moved along whenever the wheel reaches them. Each slot is
+//! an intrusive linked list of timers, which makes inserting and cancelling a timer O(1). As time
+//! advances, the timers in expiring slots are either fired or moved down to a finer level.
+//!
+//! The wheel has a resolution of one millisecond. Deadlines are rounded up, so timers never fire
+//! early.
+
+use std::collections::HashMap;
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+/// The number of bits of the tick count that each level covers.
+const LEVEL_BITS: usize = 6;
+
+/// The number of slots per level.
+const SLOTS: usize = 1 << LEVEL_BITS;
+
+/// The number of levels in the wheel.
+const LEVELS: usize = 6;
+
+/// The furthest into the future, in ticks, that a timer can be scheduled.
+const MAX_TICKS: u64 = (1 << (LEVEL_BITS * LEVELS)) - 1;
+
+/// The list of timers that are already due.
+const PENDING: usize = LEVELS * SLOTS;
+
+/// A hierarchical timer wheel.
+pub(crate) struct TimerWheel {
+    /// The instant corresponding to tick zero.
+    start: Instant,
+
+    /// The number of ticks that the wheel has advanced through.
+    elapsed: u64,
+
+    /// The registered timers, keyed by their ID.
+    entries: HashMap<usize, Entry>,
+
+    /// The first timer in each slot, followed by the list of pending timers.
+    heads: Box<[Option<usize>]>,
+
+    /// Bitmask of the non-empty slots on each level.
+    occupied: [u64; LEVELS],
+}
+
+/// A timer registered in the wheel.
+struct Entry {
+    /// The tick at which this timer fires.
+    when: u64,
+
+    /// The waker to wake when the timer fires.
+    waker: Waker,
+
+    /// Whether this timer is paused while the application is suspended.
+    pausable: bool,
+
+    /// The list that this timer is in.
+    list: usize,
+
+    /// The previous timer in the list.
+    prev: Option<usize>,
+
+    /// The next timer in the list.
+    next: Option<usize>,
+}
+
+impl TimerWheel {
+    /// Create an empty timer wheel, starting at the given time.
+    pub(crate) fn new(start: Instant) -> Self {
+        Self {
+            start,
+            elapsed: 0,
+            entries: HashMap::new(),
+            heads: vec![None; PENDING + 1].into_boxed_slice(),
+            occupied: [0; LEVELS],
+        }
+    }
+
+    /// Tell whether there are no timers in the wheel.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Insert a timer that fires somewhere between `deadline` and `deadline + tolerance`.
+    ///
+    /// A timer with a tolerance is moved to the latest tick in its window that is a multiple of
+    /// the largest power of two (in milliseconds) that fits in the tolerance. Nearby timers with
+    /// similar tolerances therefore end up on the same tick and fire in a single wakeup.
+    pub(crate) fn insert(
+        &mut self,
+        id: usize,
+        deadline: Instant,
+        tolerance: Duration,
+        pausable: bool,
+        waker: Waker,
+    ) {
+        let earliest = self.ticks_ceil(deadline);
+        let tolerance = u64::try_from(tolerance.as_millis()).unwrap_or(u64::MAX);
+
+        let when = if tolerance == 0 {
+            earliest
+        } else {
+            let granularity = 1 << (63 - tolerance.leading_zeros());
+            let latest = earliest.saturating_add(tolerance);
+            latest - latest % granularity
+        };
+
+        self.entries.insert(
+            id,
+            Entry {
+                when,
+                waker,
+                pausable,
+                list: PENDING,
+                prev: None,
+                next: None,
+            },
+        );
+        self.link(id, self.list_for(when));
+    }
+
+    /// Remove a timer from the wheel, returning its waker.
+    pub(crate) fn remove(&mut self, id: usize) -> Option<Waker> {
+        if !self.entries.contains_key(&id) {
+            return None;
+        }
+
+        self.unlink(id);
+        self.entries.remove(&id).map(|entry| entry.waker)
+    }
+
+    /// Remove all of the timers that are paused while the application is suspended, returning
+    /// their wakers.
+    pub(crate) fn take_pausable(&mut self, wakers: &mut Vec<Waker>) {
+        let ids = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.pausable)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+
+        wakers.extend(ids.into_iter().filter_map(|id| self.remove(id)));
+    }
+
+    /// Fire all of the timers that are due at `now`, and return when the next timer is due.
+    pub(crate) fn process(&mut self, now: Instant, wakers: &mut Vec<Waker>) -> Option<Instant> {
+        let now_ticks = self.ticks_floor(now);
+
+        while let Some((list, deadline)) = self.next_expiration() {
+            if deadline > now_ticks {
+                break;
+            }
+
+            self.elapsed = self.elapsed.max(deadline);
+
+            // Fire the timers in this slot, or cascade them down to a finer level.
+            while let Some(id) = self.heads[list] {
+                self.unlink(id);
+
+                let when = self.entries[&id].when;
+                if when <= now_ticks {
+                    if let Some(entry) = self.entries.remove(&id) {
+                        wakers.push(entry.waker);
+                    }
+                } else {
+                    self.link(id, self.list_for(when));
+                }
+            }
+        }
+
+        self.elapsed = self.elapsed.max(now_ticks);
+
+        // Wake up for the earliest timer in the next slot instead of the start of the slot, so
+        // that cascading a slot down a level doesn't need a wakeup of its own.
+        self.next_expiration().map(|(list, deadline)| {
+            let mut earliest = u64::MAX;
+            let mut next = self.heads[list];
+            while let Some(id) = next {
+                let entry = &self.entries[&id];
+                earliest = earliest.min(entry.when);
+                next = entry.next;
+            }
+
+            self.start + Duration::from_millis(earliest.max(deadline))
+        })
+    }
+
+    /// Get the next list to expire, and the tick at which it expires.
+    fn next_expiration(&self) -> Option<(usize, u64)> {
+        if self.heads[PENDING].is_some() {
+            return Some((PENDING, self.elapsed));
+        }
+
+        // Timers on lower levels always expire before the ones on higher levels.
+        self.occupied
+            .iter()
+            .enumerate()
+            .find(|(_, occupied)| **occupied != 0)
+            .map(|(level, &occupied)| {
+                let slot_range = 1u64 << (LEVEL_BITS * level);
+                let level_range = slot_range << LEVEL_BITS;
+
+                // Find the next occupied slot, starting from the current one.
+                let now_slot = ((self.elapsed / slot_range) % SLOTS as u64) as u32;
+                let slot = (u64::from(occupied.rotate_right(now_slot).trailing_zeros())
+                    + u64::from(now_slot))
+                    % SLOTS as u64;
+
+                let level_start = self.elapsed & !(level_range - 1);
+                let mut deadline = level_start + slot * slot_range;
+                if deadline <= self.elapsed {
+                    // The top level wraps around.
+                    deadline += level_range;
+                }
+
+                (level * SLOTS + slot as usize, deadline)
+            })
+    }
+
+    /// Get the list that a timer firing at `when` belongs in.
+    fn list_for(&self, when: u64) -> usize {
+        if when <= self.elapsed {
+            return PENDING;
+        }
+
+        // Timers beyond the end of the wheel wait at its end, and are moved on from there.
+        let when = when.min(self.elapsed + MAX_TICKS);
+
+        // The level is determined by the highest bit that differs from the current time.
+        let masked = ((self.elapsed ^ when) | (SLOTS as u64 - 1)).min(MAX_TICKS);
+        let significant = 63 - masked.leading_zeros() as usize;
+        let level = significant / LEVEL_BITS;
+        let slot = (when >> (LEVEL_BITS * level)) as usize % SLOTS;
+
+        level * SLOTS + slot
+    }
+
+    /// Add a timer to the front of a list.
+    fn link(&mut self, id: usize, list: usize) {
+        let next = self.heads[list].replace(id);
+        if let Some(next) = next {
+            self.entries.get_mut(&next).unwrap().prev = Some(id);
+        }
+
+        let entry = self.entries.get_mut(&id).unwrap();
+        entry.list = list;
+        entry.prev = None;
+        entry.next = next;
+
+        if list != PENDING {
+            self.occupied[list / SLOTS] |= 1 << (list % SLOTS);
+        }
+    }
+
+    /// Remove a timer from its list.
+    fn unlink(&mut self, id: usize) {
+        let (list, prev, next) = {
+            let entry = &self.entries[&id];
+            (entry.list, entry.prev, entry.next)
+        };
+
+        match prev {
+            Some(prev) => self.entries.get_mut(&prev).unwrap().next = next,
+            None => self.heads[list] = next,
+        }
+
+        if let Some(next) = next {
+            self.entries.get_mut(&next).unwrap().prev = prev;
+        }
+
+        if list != PENDING && self.heads[list].is_none() {
+            self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+        }
+    }
+
+    /// Convert an instant to ticks, rounding up.
+    fn ticks_ceil(&self, instant: Instant) -> u64 {
+        let nanos = instant.saturating_duration_since(self.start).as_nanos();
+        u64::try_from((nanos + 999_999) / 1_000_000).unwrap_or(u64::MAX)
+    }
+
+    /// Convert an instant to ticks, rounding down.
+    fn ticks_floor(&self, instant: Instant) -> u64 {
+        let millis = instant.saturating_duration_since(self.start).as_millis();
+        u64::try_from(millis).unwrap_or(u64::MAX)
+    }
+}
@@ -0,0 +1,896 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Recording events and replaying them.
+//!
+//! A [`Recorder`] writes every window, device and lifecycle event that the event loop receives to
+//! a file, along with the time at which it was received. A [`Replayer`] feeds a recording back to
+//! the event handlers, which makes it possible to reproduce bugs that depend on user input.
+//!
+//! Recordings can be stored in two formats:
+//!
+//! - [`Format::JsonLines`], where each line is a JSON object. The first line is a header with the
+//!   version of the format, and every line after it is a [`Record`].
+//! - [`Format::Binary`], which starts with the bytes `AWREC` and the version as a little-endian
+//!   `u32`. Every record after that is encoded with MessagePack, prefixed with its length as a
+//!   little-endian `u32`.
+//!
+//! Device IDs are not recorded. Keyboard input and scale factor changes are replayed directly to
+//! the window's handlers, since `winit` doesn't allow creating those events. The inner size writer
+//! of a replayed scale factor change isn't connected to the window.
+//!
+//! This module is only available with the `record` feature.
+
+use crate::event::{Ime, InnerSizeWriter, KeyEvent, KeyboardInput, Modifiers};
+use crate::event_loop::EventLoopWindowTarget;
+use crate::keyboard::{Key, KeyLocation, ModifiersState, PhysicalKey};
+use crate::reactor::Reactor;
+use crate::sync::ThreadSafety;
+use crate::window::Window;
+use crate::{DefaultThreadSafety, Timer};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceEvent, DeviceId, ElementState, Event, Force, MouseButton, MouseScrollDelta, RawKeyEvent,
+    Touch, TouchPhase, WindowEvent,
+};
+use winit::window::{Theme, WindowId};
+
+/// The version of the recording format.
+pub const VERSION: u32 = 1;
+
+/// The bytes at the start of a binary recording.
+const MAGIC: &[u8; 5] = b"AWREC";
+
+/// The name of the format in the header of a JSON Lines recording.
+const FORMAT_NAME: &str = "async-winit-recording";
+
+/// The format of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// One JSON object per line.
+    JsonLines,
+
+    /// A compact binary format.
+    Binary,
+}
+
+/// An event in a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// The time at which the event was received, since the recording started.
+    pub time: Duration,
+
+    /// The event.
+    pub event: RecordedEvent,
+}
+
+/// An event that can be recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    /// An event for a window.
+    Window {
+        /// The ID of the window, as returned by `u64::from(WindowId)`.
+        window: u64,
+
+        /// The event.
+        event: RecordedWindowEvent,
+    },
+
+    /// An event from a device.
+    Device(RecordedDeviceEvent),
+
+    /// The application was resumed.
+    Resumed,
+
+    /// The application was suspended.
+    Suspended,
+}
+
+/// A recorded [`WindowEvent`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum RecordedWindowEvent {
+    Resized(PhysicalSize<u32>),
+    Moved(PhysicalPosition<i32>),
+    CloseRequested,
+    Destroyed,
+    DroppedFile(PathBuf),
+    HoveredFile(PathBuf),
+    HoveredFileCancelled,
+    Focused(bool),
+    KeyboardInput {
+        event: RecordedKeyEvent,
+        is_synthetic: bool,
+    },
+    /// The modifiers, as returned by [`ModifiersState::bits`].
+    ModifiersChanged(u32),
+    Ime(Ime),
+    CursorMoved {
+        position: PhysicalPosition<f64>,
+    },
+    CursorEntered,
+    CursorLeft,
+    MouseWheel {
+        delta: MouseScrollDelta,
+        phase: TouchPhase,
+    },
+    MouseInput {
+        state: ElementState,
+        button: MouseButton,
+    },
+    TouchpadMagnify {
+        delta: f64,
+        phase: TouchPhase,
+    },
+    SmartMagnify,
+    TouchpadRotate {
+        delta: f32,
+        phase: TouchPhase,
+    },
+    TouchpadPressure {
+        pressure: f32,
+        stage: i64,
+    },
+    AxisMotion {
+        axis: u32,
+        value: f64,
+    },
+    Touch {
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+        force: Option<RecordedForce>,
+        id: u64,
+    },
+    ScaleFactorChanged {
+        scale_factor: f64,
+    },
+    ThemeChanged(Theme),
+    Occluded(bool),
+    RedrawRequested,
+}
+
+/// A recorded [`KeyEvent`](winit::event::KeyEvent).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub struct RecordedKeyEvent {
+    pub physical_key: PhysicalKey,
+    pub logical_key: Key,
+    pub text: Option<String>,
+    pub location: KeyLocation,
+    pub state: ElementState,
+    pub repeat: bool,
+}
+
+/// A recorded [`Force`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum RecordedForce {
+    Calibrated {
+        force: f64,
+        max_possible_force: f64,
+        altitude_angle: Option<f64>,
+    },
+    Normalized(f64),
+}
+
+impl RecordedKeyEvent {
+    /// Convert this back into a key event.
+    pub fn to_key_event(&self) -> KeyEvent {
+        let mut event = KeyEvent::new(self.physical_key, self.logical_key.clone(), self.state);
+        event.text = self.text.as_deref().map(Into::into);
+        event.location = self.location;
+        event.repeat = self.repeat;
+        event
+    }
+}
+
+/// A recorded [`DeviceEvent`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(missing_docs)]
+pub enum RecordedDeviceEvent {
+    Added,
+    Removed,
+    MouseMotion { delta: (f64, f64) },
+    MouseWheel { delta: MouseScrollDelta },
+    Motion { axis: u32, value: f64 },
+    Button { button: u32, state: ElementState },
+    Key(RawKeyEvent),
+}
+
+impl RecordedEvent {
+    /// Convert an event from the event loop, if it can be recorded.
+    pub fn from_event<T>(event: &Event<T>) -> Option<Self> {
+        match event {
+            Event::WindowEvent { window_id, event } => Some(Self::Window {
+                window: (*window_id).into(),
+                event: RecordedWindowEvent::from_window_event(event)?,
+            }),
+            Event::DeviceEvent { event, .. } => {
+                Some(Self::Device(RecordedDeviceEvent::from_device_event(event)))
+            }
+            Event::Resumed => Some(Self::Resumed),
+            Event::Suspended => Some(Self::Suspended),
+            _ => None,
+        }
+    }
+}
+
+impl RecordedWindowEvent {
+    /// Convert a window event, if it can be recorded.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::ActivationTokenDone { .. } => return None,
+            WindowEvent::Resized(size) => Self::Resized(*size),
+            WindowEvent::Moved(position) => Self::Moved(*position),
+            WindowEvent::CloseRequested => Self::CloseRequested,
+            WindowEvent::Destroyed => Self::Destroyed,
+            WindowEvent::DroppedFile(path) => Self::DroppedFile(path.clone()),
+            WindowEvent::HoveredFile(path) => Self::HoveredFile(path.clone()),
+            WindowEvent::HoveredFileCancelled => Self::HoveredFileCancelled,
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            WindowEvent::KeyboardInput {
+                event,
+                is_synthetic,
+                ..
+            } => Self::KeyboardInput {
+                event: RecordedKeyEvent {
+                    physical_key: event.physical_key,
+                    logical_key: event.logical_key.clone(),
+                    text: event.text.as_ref().map(|text| text.to_string()),
+                    location: event.location,
+                    state: event.state,
+                    repeat: event.repeat,
+                },
+                is_synthetic: *is_synthetic,
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                Self::ModifiersChanged(modifiers.state().bits())
+            }
+            WindowEvent::Ime(ime) => Self::Ime(ime.clone()),
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                position: *position,
+            },
+            WindowEvent::CursorEntered { .. } => Self::CursorEntered,
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, phase, .. } => Self::MouseWheel {
+                delta: *delta,
+                phase: *phase,
+            },
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseInput {
+                state: *state,
+                button: *button,
+            },
+            WindowEvent::TouchpadMagnify { delta, phase, .. } => Self::TouchpadMagnify {
+                delta: *delta,
+                phase: *phase,
+            },
+            WindowEvent::SmartMagnify { .. } => Self::SmartMagnify,
+            WindowEvent::TouchpadRotate { delta, phase, .. } => Self::TouchpadRotate {
+                delta: *delta,
+                phase: *phase,
+            },
+            WindowEvent::TouchpadPressure {
+                pressure, stage, ..
+            } => Self::TouchpadPressure {
+                pressure: *pressure,
+                stage: *stage,
+            },
+            WindowEvent::AxisMotion { axis, value, .. } => Self::AxisMotion {
+                axis: *axis,
+                value: *value,
+            },
+            WindowEvent::Touch(touch) => Self::Touch {
+                phase: touch.phase,
+                location: touch.location,
+                force: touch.force.map(|force| match force {
+                    Force::Calibrated {
+                        force,
+                        max_possible_force,
+                        altitude_angle,
+                    } => RecordedForce::Calibrated {
+                        force,
+                        max_possible_force,
+                        altitude_angle,
+                    },
+                    Force::Normalized(force) => RecordedForce::Normalized(force),
+                }),
+                id: touch.id,
+            },
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => Self::ScaleFactorChanged {
+                scale_factor: *scale_factor,
+            },
+            WindowEvent::ThemeChanged(theme) => Self::ThemeChanged(*theme),
+            WindowEvent::Occluded(occluded) => Self::Occluded(*occluded),
+            WindowEvent::RedrawRequested => Self::RedrawRequested,
+        })
+    }
+
+    /// Convert this back into a window event, if `winit` allows creating it.
+    ///
+    /// Device IDs are replaced with a dummy ID.
+    pub fn to_window_event(&self) -> Option<WindowEvent> {
+        // SAFETY: The dummy ID is only compared against other device IDs.
+        let device_id = unsafe { DeviceId::dummy() };
+
+        Some(match self {
+            Self::Resized(size) => WindowEvent::Resized(*size),
+            Self::Moved(position) => WindowEvent::Moved(*position),
+            Self::CloseRequested => WindowEvent::CloseRequested,
+            Self::Destroyed => WindowEvent::Destroyed,
+            Self::DroppedFile(path) => WindowEvent::DroppedFile(path.clone()),
+            Self::HoveredFile(path) => WindowEvent::HoveredFile(path.clone()),
+            Self::HoveredFileCancelled => WindowEvent::HoveredFileCancelled,
+            Self::Focused(focused) => WindowEvent::Focused(*focused),
+            Self::KeyboardInput { .. } => return None,
+            Self::ModifiersChanged(bits) => WindowEvent::ModifiersChanged(Modifiers::from(
+                ModifiersState::from_bits_truncate(*bits),
+            )),
+            Self::Ime(ime) => WindowEvent::Ime(ime.clone()),
+            Self::CursorMoved { position } => WindowEvent::CursorMoved {
+                device_id,
+                position: *position,
+            },
+            Self::CursorEntered => WindowEvent::CursorEntered { device_id },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseWheel { delta, phase } => WindowEvent::MouseWheel {
+                device_id,
+                delta: *delta,
+                phase: *phase,
+            },
+            Self::MouseInput { state, button } => WindowEvent::MouseInput {
+                device_id,
+                state: *state,
+                button: *button,
+            },
+            Self::TouchpadMagnify { delta, phase } => WindowEvent::TouchpadMagnify {
+                device_id,
+                delta: *delta,
+                phase: *phase,
+            },
+            Self::SmartMagnify => WindowEvent::SmartMagnify { device_id },
+            Self::TouchpadRotate { delta, phase } => WindowEvent::TouchpadRotate {
+                device_id,
+                delta: *delta,
+                phase: *phase,
+            },
+            Self::TouchpadPressure { pressure, stage } => WindowEvent::TouchpadPressure {
+                device_id,
+                pressure: *pressure,
+                stage: *stage,
+            },
+            Self::AxisMotion { axis, value } => WindowEvent::AxisMotion {
+                device_id,
+                axis: *axis,
+                value: *value,
+            },
+            Self::Touch {
+                phase,
+                location,
+                force,
+                id,
+            } => WindowEvent::Touch(Touch {
+                device_id,
+                phase: *phase,
+                location: *location,
+                force: force.map(|force| match force {
+                    RecordedForce::Calibrated {
+                        force,
+                        max_possible_force,
+                        altitude_angle,
+                    } => Force::Calibrated {
+                        force,
+                        max_possible_force,
+                        altitude_angle,
+                    },
+                    RecordedForce::Normalized(force) => Force::Normalized(force),
+                }),
+                id: *id,
+            }),
+            Self::ScaleFactorChanged { .. } => return None,
+            Self::ThemeChanged(theme) => WindowEvent::ThemeChanged(*theme),
+            Self::Occluded(occluded) => WindowEvent::Occluded(*occluded),
+            Self::RedrawRequested => WindowEvent::RedrawRequested,
+        })
+    }
+}
+
+impl RecordedDeviceEvent {
+    /// Convert a device event.
+    pub fn from_device_event(event: &DeviceEvent) -> Self {
+        match event {
+            DeviceEvent::Added => Self::Added,
+            DeviceEvent::Removed => Self::Removed,
+            DeviceEvent::MouseMotion { delta } => Self::MouseMotion { delta: *delta },
+            DeviceEvent::MouseWheel { delta } => Self::MouseWheel { delta: *delta },
+            DeviceEvent::Motion { axis, value } => Self::Motion {
+                axis: *axis,
+                value: *value,
+            },
+            DeviceEvent::Button { button, state } => Self::Button {
+                button: *button,
+                state: *state,
+            },
+            DeviceEvent::Key(key) => Self::Key(key.clone()),
+        }
+    }
+
+    /// Convert this back into a device event.
+    pub fn to_device_event(&self) -> DeviceEvent {
+        match self {
+            Self::Added => DeviceEvent::Added,
+            Self::Removed => DeviceEvent::Removed,
+            Self::MouseMotion { delta } => DeviceEvent::MouseMotion { delta: *delta },
+            Self::MouseWheel { delta } => DeviceEvent::MouseWheel { delta: *delta },
+            Self::Motion { axis, value } => DeviceEvent::Motion {
+                axis: *axis,
+                value: *value,
+            },
+            Self::Button { button, state } => DeviceEvent::Button {
+                button: *button,
+                state: *state,
+            },
+            Self::Key(key) => DeviceEvent::Key(key.clone()),
+        }
+    }
+}
+
+/// The header of a JSON Lines recording.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+/// Writes records to a recording.
+pub struct RecordWriter<W: Write> {
+    /// The underlying writer.
+    writer: W,
+
+    /// The format to write.
+    format: Format,
+}
+
+impl<W: Write> fmt::Debug for RecordWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordWriter")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Start a new recording, writing its header.
+    pub fn new(mut writer: W, format: Format) -> io::Result<Self> {
+        match format {
+            Format::JsonLines => {
+                let header = Header {
+                    format: FORMAT_NAME.to_string(),
+                    version: VERSION,
+                };
+                serde_json::to_writer(&mut writer, &header)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&VERSION.to_le_bytes())?;
+            }
+        }
+
+        Ok(Self { writer, format })
+    }
+
+    /// Write a record.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            Format::JsonLines => {
+                serde_json::to_writer(&mut self.writer, record)?;
+                self.writer.write_all(b"\n")
+            }
+            Format::Binary => {
+                let bytes = rmp_serde::to_vec(record).map_err(invalid_data)?;
+                let len = u32::try_from(bytes.len()).map_err(invalid_data)?;
+                self.writer.write_all(&len.to_le_bytes())?;
+                self.writer.write_all(&bytes)
+            }
+        }
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Get the underlying writer back.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records in a recording.
+///
+/// The format of the recording is detected from its header.
+pub struct RecordReader<R: Read> {
+    /// The underlying reader.
+    reader: BufReader<R>,
+
+    /// The format being read.
+    format: Format,
+
+    /// A buffer for the current line or record.
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> fmt::Debug for RecordReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordReader")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Start reading a recording, checking its header.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        let format = if reader.fill_buf()?.starts_with(MAGIC) {
+            let mut header = [0u8; MAGIC.len() + 4];
+            reader.read_exact(&mut header)?;
+            let version = u32::from_le_bytes(header[MAGIC.len()..].try_into().unwrap());
+            check_version(version)?;
+            Format::Binary
+        } else {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let header: Header = serde_json::from_str(&line)?;
+            if header.format != FORMAT_NAME {
+                return Err(invalid_data("not an async-winit recording"));
+            }
+            check_version(header.version)?;
+            Format::JsonLines
+        };
+
+        Ok(Self {
+            reader,
+            format,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Get the format of the recording.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Read the next record, or `None` at the end of the recording.
+    pub fn read(&mut self) -> io::Result<Option<Record>> {
+        self.buffer.clear();
+
+        match self.format {
+            Format::JsonLines => loop {
+                if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+                    return Ok(None);
+                }
+
+                // Skip blank lines.
+                if self.buffer.iter().all(u8::is_ascii_whitespace) {
+                    self.buffer.clear();
+                    continue;
+                }
+
+                return Ok(Some(serde_json::from_slice(&self.buffer)?));
+            },
+            Format::Binary => {
+                let mut len = [0u8; 4];
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                self.reader.read_exact(&mut len)?;
+
+                self.buffer.resize(u32::from_le_bytes(len) as usize, 0);
+                self.reader.read_exact(&mut self.buffer)?;
+                rmp_serde::from_slice(&self.buffer)
+                    .map(Some)
+                    .map_err(invalid_data)
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    if version > VERSION {
+        return Err(invalid_data(format!(
+            "unsupported recording version {version}"
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// The recording that the reactor is currently writing to.
+pub(crate) struct Sink {
+    /// The writer for the recording.
+    writer: RecordWriter<Box<dyn Write + Send>>,
+
+    /// The time at which recording started.
+    start: Instant,
+
+    /// The first error that occurred while writing.
+    error: Option<io::Error>,
+}
+
+impl Sink {
+    /// Record an event.
+    pub(crate) fn record<T>(&mut self, event: &Event<T>, timestamp: Instant) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Some(event) = RecordedEvent::from_event(event) {
+            let record = Record {
+                time: timestamp.saturating_duration_since(self.start),
+                event,
+            };
+
+            if let Err(err) = self.writer.write(&record) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    /// Finish the recording.
+    fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+impl<TS: ThreadSafety> EventLoopWindowTarget<TS> {
+    /// Start recording the events that the event loop receives.
+    ///
+    /// Recording stops once the returned [`Recorder`] is dropped. Only one recording can be made at
+    /// a time. The writer is buffered, so the recording is only complete once it is finished.
+    pub fn start_recording(
+        &self,
+        writer: impl Write + Send + 'static,
+        format: Format,
+    ) -> io::Result<Recorder<TS>> {
+        let reactor = Reactor::<TS>::get();
+        // Events are recorded while the reactor is locked, so don't make a system call for each one.
+        let writer: Box<dyn Write + Send> = Box::new(BufWriter::new(writer));
+        let sink = Sink {
+            writer: RecordWriter::new(writer, format)?,
+            start: reactor.now(),
+            error: None,
+        };
+
+        if !reactor.start_recording(sink) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a recording is already in progress",
+            ));
+        }
+
+        Ok(Recorder { reactor })
+    }
+}
+
+/// A recording in progress.
+///
+/// This is returned by [`EventLoopWindowTarget::start_recording`]. Recording stops once this is
+/// dropped.
+pub struct Recorder<TS: ThreadSafety = DefaultThreadSafety> {
+    /// The reactor that is recording.
+    reactor: TS::Rc<Reactor<TS>>,
+}
+
+impl<TS: ThreadSafety> fmt::Debug for Recorder<TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Recorder { .. }")
+    }
+}
+
+impl<TS: ThreadSafety> Recorder<TS> {
+    /// Stop recording and flush the recording.
+    ///
+    /// This returns the first error that occurred while writing the recording, if any.
+    pub fn finish(self) -> io::Result<()> {
+        match self.reactor.stop_recording() {
+            Some(sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Drop for Recorder<TS> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.reactor.stop_recording() {
+            sink.finish().ok();
+        }
+    }
+}
+
+/// Feeds a recording back to the event handlers.
+pub struct Replayer<TS: ThreadSafety = DefaultThreadSafety> {
+    /// The records to replay.
+    records: Vec<Record>,
+
+    /// Maps recorded window IDs to live windows.
+    windows: HashMap<u64, WindowId>,
+
+    /// How much faster than the original timing to replay the events.
+    speed: f64,
+
+    /// The reactor to replay the events to.
+    reactor: TS::Rc<Reactor<TS>>,
+}
+
+impl<TS: ThreadSafety> fmt::Debug for Replayer<TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Replayer")
+            .field("records", &self.records.len())
+            .field("speed", &self.speed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<TS: ThreadSafety> Replayer<TS> {
+    /// Create a replayer for a list of records.
+    pub fn new(records: Vec<Record>) -> Self {
+        Self {
+            records,
+            windows: HashMap::new(),
+            speed: 1.0,
+            reactor: Reactor::<TS>::get(),
+        }
+    }
+
+    /// Read a recording to replay.
+    pub fn read(reader: impl Read) -> io::Result<Self> {
+        let records = RecordReader::new(reader)?.collect::<io::Result<_>>()?;
+        Ok(Self::new(records))
+    }
+
+    /// Replay the events of a recorded window to a live window.
+    ///
+    /// Events for recorded windows that aren't mapped are replayed to the live window with the same
+    /// ID, if there is one.
+    pub fn map_window(&mut self, recorded: u64, window: &Window<TS>) -> &mut Self {
+        self.windows.insert(recorded, window.id());
+        self
+    }
+
+    /// Set how much faster than the original timing to replay the events.
+    ///
+    /// The default is `1.0`, which keeps the original timing. Use `f64::INFINITY` to replay the
+    /// events without waiting between them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `speed` is not positive.
+    pub fn set_speed(&mut self, speed: f64) -> &mut Self {
+        assert!(speed > 0.0, "replay speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    /// Get the records that will be replayed.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Replay the events.
+    ///
+    /// Events are delivered to the handlers the same way that events from the event loop are.
+    /// Keyboard input and scale factor changes are delivered directly to the window's handlers,
+    /// and are skipped if the window doesn't exist. Device IDs are replaced with a dummy ID.
+    pub async fn replay(&self) {
+        // SAFETY: The dummy ID is only compared against other device IDs.
+        let device_id = unsafe { DeviceId::dummy() };
+
+        let start = self.reactor.now();
+        let base = self
+            .records
+            .first()
+            .map_or(Duration::ZERO, |record| record.time);
+
+        for record in &self.records {
+            if self.speed.is_finite() {
+                let offset = record.time.saturating_sub(base).div_f64(self.speed);
+                Timer::<TS>::at(start + offset).await;
+            }
+
+            let event = match &record.event {
+                RecordedEvent::Window { window, event } => {
+                    let window_id = self
+                        .windows
+                        .get(window)
+                        .copied()
+                        .unwrap_or_else(|| WindowId::from(*window));
+
+                    match event {
+                        RecordedWindowEvent::KeyboardInput {
+                            event,
+                            is_synthetic,
+                        } => {
+                            if let Some(registration) = self.reactor.window_registration(window_id)
+                            {
+                                let input = KeyboardInput {
+                                    device_id,
+                                    event: event.to_key_event(),
+                                    is_synthetic: *is_synthetic,
+                                };
+                                self.reactor
+                                    .inject_keyboard_input(window_id, &registration, input)
+                                    .await;
+                            }
+                            continue;
+                        }
+                        RecordedWindowEvent::ScaleFactorChanged { scale_factor } => {
+                            if let Some(registration) = self.reactor.window_registration(window_id)
+                            {
+                                registration
+                                    .signal_scale_factor_changed(
+                                        *scale_factor,
+                                        InnerSizeWriter::detached(),
+                                        self.reactor.now(),
+                                    )
+                                    .await;
+                            }
+                            continue;
+                        }
+                        event => match event.to_window_event() {
+                            Some(event) => Event::WindowEvent { window_id, event },
+                            None => continue,
+                        },
+                    }
+                }
+                RecordedEvent::Device(event) => Event::DeviceEvent {
+                    device_id,
+                    event: event.to_device_event(),
+                },
+                RecordedEvent::Resumed => Event::Resumed,
+                RecordedEvent::Suspended => Event::Suspended,
+            };
+
+            self.reactor.inject(event).await;
+        }
+    }
+}
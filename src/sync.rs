@@ -22,11 +22,12 @@ pub(crate) use __private::__ThreadSafety;
 use core::cell::{Cell, RefCell, RefMut};
 use core::convert::Infallible;
 use core::future::Future;
-use core::ops::Add;
+use core::ops::{Add, Sub};
 
 use std::collections::VecDeque;
 use std::rc::Rc;
-use std::sync::atomic;
+use std::sync::{atomic, PoisonError};
+use std::task::Waker;
 use std::thread;
 
 use unsend::channel as us_channel;
@@ -44,7 +45,7 @@ pub type DefaultThreadSafety = _DefaultTS;
 
 /// A token that can be used to indicate whether the current implementation should be thread-safe or
 /// not.
-pub trait ThreadSafety: __ThreadSafety + std::fmt::Debug {}
+pub trait ThreadSafety: __ThreadSafety + std::fmt::Debug + 'static {}
 
 /// Use thread-unsafe primitives.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -60,6 +61,7 @@ impl __ThreadSafety for ThreadUnsafe {
     type AtomicUsize = Cell<usize>;
     type AtomicU64 = Cell<u64>;
     type AtomicI64 = Cell<i64>;
+    type AtomicWaker = Cell<Option<Waker>>;
 
     type Receiver<T> = us_channel::Receiver<T>;
     type Sender<T> = us_channel::Sender<T>;
@@ -68,42 +70,74 @@ impl __ThreadSafety for ThreadUnsafe {
     type ConcurrentQueue<T> = RefCell<VecDeque<T>>;
     type Mutex<T> = RefCell<T>;
     type OnceLock<T> = once_cell::unsync::OnceCell<T>;
+    type Snapshot<T> = RefCell<Option<Rc<T>>>;
 
     fn channel_bounded<T>(_capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
         us_channel::channel()
     }
 
     fn get_reactor() -> Self::Rc<Reactor<Self>> {
-        use once_cell::sync::OnceCell;
-
-        /// The thread ID of the thread that created the reactor.
-        static REACTOR_THREAD_ID: OnceCell<thread::ThreadId> = OnceCell::new();
-
-        std::thread_local! {
-            static REACTOR: RefCell<Option<std::rc::Rc<Reactor<ThreadUnsafe>>>> = RefCell::new(None);
-        }
-
-        // Try to set the thread ID.
-        let thread_id = thread_id();
-        let reactor_thread_id = REACTOR_THREAD_ID.get_or_init(|| thread_id);
-
-        if thread_id != *reactor_thread_id {
-            panic!("The reactor must be created on the main thread");
-        }
-
-        REACTOR.with(|reactor| {
+        UNSAFE_REACTOR.with(|reactor| {
             reactor
                 .borrow_mut()
-                .get_or_insert_with(|| std::rc::Rc::new(Reactor::new()))
+                .get_or_insert_with(|| {
+                    // Claim the reactor for this thread.
+                    let thread_id = thread_id();
+                    let mut owner = UNSAFE_REACTOR_THREAD
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+
+                    if *owner.get_or_insert(thread_id) != thread_id {
+                        panic!("The reactor must be created on the main thread");
+                    }
+
+                    std::rc::Rc::new(Reactor::new())
+                })
                 .clone()
         })
     }
+
+    fn try_get_reactor() -> Option<Self::Rc<Reactor<Self>>> {
+        UNSAFE_REACTOR.with(|reactor| reactor.borrow().clone())
+    }
+
+    #[cfg(feature = "macros")]
+    fn drop_reactor() -> usize {
+        let reactor = UNSAFE_REACTOR.with(|reactor| {
+            let mut reactor = reactor.borrow_mut();
+            match &*reactor {
+                Some(handle) if std::rc::Rc::strong_count(handle) > 1 => {
+                    Err(std::rc::Rc::strong_count(handle) - 1)
+                }
+                _ => Ok(reactor.take()),
+            }
+        });
+
+        match reactor {
+            Ok(Some(reactor)) => {
+                drop(reactor);
+                *UNSAFE_REACTOR_THREAD
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = None;
+                0
+            }
+            Ok(None) => 0,
+            Err(leaked) => leaked,
+        }
+    }
 }
 
-pub(crate) type MutexGuard<'a, T, TS> =
-    <<TS as __ThreadSafety>::Mutex<T> as __private::Mutex<T>>::Lock<'a>;
+/// The thread that owns the thread-unsafe reactor.
+static UNSAFE_REACTOR_THREAD: std::sync::Mutex<Option<thread::ThreadId>> =
+    std::sync::Mutex::new(None);
+
+std::thread_local! {
+    /// The thread-unsafe reactor, if this thread owns it.
+    static UNSAFE_REACTOR: RefCell<Option<std::rc::Rc<Reactor<ThreadUnsafe>>>> =
+        const { RefCell::new(None) };
+}
 
-fn thread_id() -> thread::ThreadId {
+pub(crate) fn thread_id() -> thread::ThreadId {
     // Get the address of a thread-local variable.
     std::thread_local! {
         static THREAD_ID: Cell<Option<thread::ThreadId>> = Cell::new(None);
@@ -144,6 +178,58 @@ impl<T: Copy> __private::Atomic<T> for Cell<T> {
         self.set(old + value);
         old
     }
+
+    fn fetch_sub(&self, value: T, _order: atomic::Ordering) -> T
+    where
+        T: Sub<Output = T>,
+    {
+        let old = self.get();
+        self.set(old - value);
+        old
+    }
+
+    fn swap(&self, value: T, _order: atomic::Ordering) -> T {
+        self.replace(value)
+    }
+
+    fn compare_exchange(
+        &self,
+        current: T,
+        new: T,
+        _success: atomic::Ordering,
+        _failure: atomic::Ordering,
+    ) -> Result<T, T>
+    where
+        T: PartialEq,
+    {
+        let old = self.get();
+        if old == current {
+            self.set(new);
+            Ok(old)
+        } else {
+            Err(old)
+        }
+    }
+}
+
+impl __private::AtomicWaker for Cell<Option<Waker>> {
+    fn new() -> Self {
+        Cell::new(None)
+    }
+
+    fn register(&self, waker: &Waker) {
+        // If the waker is the same as ours, no need to clone.
+        match self.take() {
+            Some(current) if current.will_wake(waker) => self.set(Some(current)),
+            _ => self.set(Some(waker.clone())),
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
 }
 
 impl<T> __private::Sender<T> for us_channel::Sender<T> {
@@ -260,7 +346,23 @@ impl<T> __private::OnceLock<T> for once_cell::unsync::OnceCell<T> {
     }
 }
 
+impl<T> __private::Snapshot<T, Rc<T>> for RefCell<Option<Rc<T>>> {
+    fn new(value: Option<Rc<T>>) -> Self {
+        Self::new(value)
+    }
+
+    fn load(&self) -> Option<Rc<T>> {
+        self.borrow().clone()
+    }
+
+    fn store(&self, value: Option<Rc<T>>) {
+        *self.borrow_mut() = value;
+    }
+}
+
 impl<T> __private::Rc<T> for std::rc::Rc<T> {
+    type Weak = std::rc::Weak<T>;
+
     fn new(value: T) -> Self {
         Self::new(value)
     }
@@ -272,6 +374,18 @@ impl<T> __private::Rc<T> for std::rc::Rc<T> {
     fn strong_count(this: &Self) -> usize {
         std::rc::Rc::<T>::strong_count(this)
     }
+
+    fn ptr_eq(this: &Self, other: &Self) -> bool {
+        std::rc::Rc::<T>::ptr_eq(this, other)
+    }
+
+    fn downgrade(this: &Self) -> Self::Weak {
+        std::rc::Rc::<T>::downgrade(this)
+    }
+
+    fn upgrade(weak: &Self::Weak) -> Option<Self> {
+        weak.upgrade()
+    }
 }
 
 #[cfg(feature = "thread_safe")]
@@ -282,6 +396,10 @@ pub(crate) mod thread_safe {
     use std::sync::atomic;
     use std::sync::{Arc, Mutex};
 
+    /// The thread-safe reactor.
+    static REACTOR: once_cell::sync::OnceCell<Arc<Reactor<ThreadSafe>>> =
+        once_cell::sync::OnceCell::new();
+
     /// Use thread-safe primitives.
     #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct ThreadSafe {
@@ -296,6 +414,7 @@ pub(crate) mod thread_safe {
         type AtomicI64 = atomic::AtomicI64;
         type AtomicUsize = atomic::AtomicUsize;
         type AtomicU64 = atomic::AtomicU64;
+        type AtomicWaker = atomic_waker::AtomicWaker;
 
         type Sender<T> = async_channel::Sender<T>;
         type Receiver<T> = async_channel::Receiver<T>;
@@ -304,6 +423,7 @@ pub(crate) mod thread_safe {
         type Mutex<T> = Mutex<T>;
         type OnceLock<T> = once_cell::sync::OnceCell<T>;
         type Rc<T> = Arc<T>;
+        type Snapshot<T> = arc_swap::ArcSwapOption<T>;
 
         fn channel_bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
             async_channel::bounded(capacity)
@@ -312,12 +432,27 @@ pub(crate) mod thread_safe {
         where
             Self: super::ThreadSafety,
         {
-            use once_cell::sync::OnceCell;
-
-            static REACTOR: OnceCell<Arc<Reactor<ThreadSafe>>> = OnceCell::new();
+            #[cfg(feature = "macros")]
+            if let Some(reactor) = crate::test_harness::thread_safe::get_reactor() {
+                return reactor;
+            }
 
             REACTOR.get_or_init(|| Arc::new(Reactor::new())).clone()
         }
+
+        fn try_get_reactor() -> Option<Self::Rc<Reactor<Self>>> {
+            #[cfg(feature = "macros")]
+            if let Some(reactor) = crate::test_harness::thread_safe::try_get_reactor() {
+                return reactor;
+            }
+
+            REACTOR.get().cloned()
+        }
+
+        #[cfg(feature = "macros")]
+        fn drop_reactor() -> usize {
+            crate::test_harness::thread_safe::drop_reactor()
+        }
     }
 
     impl __private::Atomic<i64> for atomic::AtomicI64 {
@@ -329,6 +464,10 @@ pub(crate) mod thread_safe {
             self.fetch_add(value, order)
         }
 
+        fn fetch_sub(&self, value: i64, order: atomic::Ordering) -> i64 {
+            self.fetch_sub(value, order)
+        }
+
         fn load(&self, order: atomic::Ordering) -> i64 {
             self.load(order)
         }
@@ -336,6 +475,20 @@ pub(crate) mod thread_safe {
         fn store(&self, value: i64, order: atomic::Ordering) {
             self.store(value, order)
         }
+
+        fn swap(&self, value: i64, order: atomic::Ordering) -> i64 {
+            self.swap(value, order)
+        }
+
+        fn compare_exchange(
+            &self,
+            current: i64,
+            new: i64,
+            success: atomic::Ordering,
+            failure: atomic::Ordering,
+        ) -> Result<i64, i64> {
+            self.compare_exchange(current, new, success, failure)
+        }
     }
 
     impl __private::Atomic<usize> for atomic::AtomicUsize {
@@ -347,6 +500,10 @@ pub(crate) mod thread_safe {
             self.fetch_add(value, order)
         }
 
+        fn fetch_sub(&self, value: usize, order: atomic::Ordering) -> usize {
+            self.fetch_sub(value, order)
+        }
+
         fn load(&self, order: atomic::Ordering) -> usize {
             self.load(order)
         }
@@ -354,6 +511,20 @@ pub(crate) mod thread_safe {
         fn store(&self, value: usize, order: atomic::Ordering) {
             self.store(value, order)
         }
+
+        fn swap(&self, value: usize, order: atomic::Ordering) -> usize {
+            self.swap(value, order)
+        }
+
+        fn compare_exchange(
+            &self,
+            current: usize,
+            new: usize,
+            success: atomic::Ordering,
+            failure: atomic::Ordering,
+        ) -> Result<usize, usize> {
+            self.compare_exchange(current, new, success, failure)
+        }
     }
 
     impl __private::Atomic<u64> for atomic::AtomicU64 {
@@ -365,6 +536,10 @@ pub(crate) mod thread_safe {
             self.fetch_add(value, order)
         }
 
+        fn fetch_sub(&self, value: u64, order: atomic::Ordering) -> u64 {
+            self.fetch_sub(value, order)
+        }
+
         fn load(&self, order: atomic::Ordering) -> u64 {
             self.load(order)
         }
@@ -372,6 +547,34 @@ pub(crate) mod thread_safe {
         fn store(&self, value: u64, order: atomic::Ordering) {
             self.store(value, order)
         }
+
+        fn swap(&self, value: u64, order: atomic::Ordering) -> u64 {
+            self.swap(value, order)
+        }
+
+        fn compare_exchange(
+            &self,
+            current: u64,
+            new: u64,
+            success: atomic::Ordering,
+            failure: atomic::Ordering,
+        ) -> Result<u64, u64> {
+            self.compare_exchange(current, new, success, failure)
+        }
+    }
+
+    impl __private::AtomicWaker for atomic_waker::AtomicWaker {
+        fn new() -> Self {
+            Self::new()
+        }
+
+        fn register(&self, waker: &Waker) {
+            self.register(waker)
+        }
+
+        fn wake(&self) {
+            self.wake()
+        }
     }
 
     impl<T> __private::Sender<T> for async_channel::Sender<T> {
@@ -467,7 +670,23 @@ pub(crate) mod thread_safe {
         }
     }
 
+    impl<T> __private::Snapshot<T, Arc<T>> for arc_swap::ArcSwapOption<T> {
+        fn new(value: Option<Arc<T>>) -> Self {
+            Self::new(value)
+        }
+
+        fn load(&self) -> Option<Arc<T>> {
+            self.load_full()
+        }
+
+        fn store(&self, value: Option<Arc<T>>) {
+            self.store(value)
+        }
+    }
+
     impl<T> __private::Rc<T> for Arc<T> {
+        type Weak = std::sync::Weak<T>;
+
         fn new(value: T) -> Self {
             Self::new(value)
         }
@@ -479,14 +698,27 @@ pub(crate) mod thread_safe {
         fn strong_count(this: &Self) -> usize {
             std::sync::Arc::<T>::strong_count(this)
         }
+
+        fn ptr_eq(this: &Self, other: &Self) -> bool {
+            std::sync::Arc::<T>::ptr_eq(this, other)
+        }
+
+        fn downgrade(this: &Self) -> Self::Weak {
+            std::sync::Arc::<T>::downgrade(this)
+        }
+
+        fn upgrade(weak: &Self::Weak) -> Option<Self> {
+            weak.upgrade()
+        }
     }
 }
 
 pub(crate) mod __private {
     use core::fmt::{Debug, Display};
     use core::future::Future;
-    use core::ops::{Add, Deref, DerefMut};
+    use core::ops::{Add, Deref, DerefMut, Sub};
     use core::sync::atomic;
+    use core::task::Waker;
 
     #[doc(hidden)]
     pub trait __ThreadSafety: Sized {
@@ -495,6 +727,7 @@ pub(crate) mod __private {
         type AtomicI64: Atomic<i64>;
         type AtomicUsize: Atomic<usize>;
         type AtomicU64: Atomic<u64>;
+        type AtomicWaker: AtomicWaker;
 
         type Sender<T>: Sender<T>;
         type Receiver<T>: Receiver<T>;
@@ -503,11 +736,27 @@ pub(crate) mod __private {
         type Mutex<T>: Mutex<T>;
         type OnceLock<T>: OnceLock<T>;
         type Rc<T>: Rc<T>;
+        type Snapshot<T>: Snapshot<T, Self::Rc<T>>;
 
         fn channel_bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>);
         fn get_reactor() -> Self::Rc<crate::reactor::Reactor<Self>>
         where
             Self: super::ThreadSafety;
+
+        /// Get the reactor if it exists, without creating it.
+        fn try_get_reactor() -> Option<Self::Rc<crate::reactor::Reactor<Self>>>
+        where
+            Self: super::ThreadSafety;
+
+        /// Destroy the reactor, so that a new one is created the next time it is needed.
+        ///
+        /// Returns the number of other handles to the reactor that are still alive. Since only one
+        /// reactor can exist at a time, it is kept if there are any, and handed out again the next
+        /// time it is needed.
+        #[cfg(feature = "macros")]
+        fn drop_reactor() -> usize
+        where
+            Self: super::ThreadSafety;
     }
 
     #[doc(hidden)]
@@ -518,6 +767,26 @@ pub(crate) mod __private {
         fn fetch_add(&self, value: T, order: atomic::Ordering) -> T
         where
             T: Add<Output = T>;
+        fn fetch_sub(&self, value: T, order: atomic::Ordering) -> T
+        where
+            T: Sub<Output = T>;
+        fn swap(&self, value: T, order: atomic::Ordering) -> T;
+        fn compare_exchange(
+            &self,
+            current: T,
+            new: T,
+            success: atomic::Ordering,
+            failure: atomic::Ordering,
+        ) -> Result<T, T>
+        where
+            T: PartialEq;
+    }
+
+    #[doc(hidden)]
+    pub trait AtomicWaker {
+        fn new() -> Self;
+        fn register(&self, waker: &Waker);
+        fn wake(&self);
     }
 
     #[doc(hidden)]
@@ -579,8 +848,20 @@ pub(crate) mod __private {
 
     #[doc(hidden)]
     pub trait Rc<T>: Clone + Deref<Target = T> {
+        type Weak: Clone;
+
         fn new(value: T) -> Self;
         fn get_mut(this: &mut Self) -> Option<&mut T>;
         fn strong_count(this: &Self) -> usize;
+        fn ptr_eq(this: &Self, other: &Self) -> bool;
+        fn downgrade(this: &Self) -> Self::Weak;
+        fn upgrade(weak: &Self::Weak) -> Option<Self>;
+    }
+
+    #[doc(hidden)]
+    pub trait Snapshot<T, R: Rc<T>> {
+        fn new(value: Option<R>) -> Self;
+        fn load(&self) -> Option<R>;
+        fn store(&self, value: Option<R>);
     }
 }
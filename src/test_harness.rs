@@ -0,0 +1,260 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Support code for the `#[async_winit::test]` attribute.
+
+use crate::event_loop::{EventLoopBuilder, EventLoopWindowTarget};
+use crate::filter::ReturnOrFinish;
+use crate::platform::run_return::EventLoopExtRunOnDemand;
+use crate::sync::ThreadSafety;
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures_lite::future;
+
+/// Only one reactor can exist at a time, so tests are run one at a time.
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Whether a test has been run in this process.
+///
+/// Once one has, reactors that would otherwise live for the rest of the process are kept by the
+/// test harness instead, so that each test gets its own.
+static IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// The number of handles to the reactor that earlier tests leaked.
+static LEAKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Run a test inside of an event loop.
+///
+/// This is called by the code generated by `#[async_winit::test]`. Panics from the test are
+/// propagated to the caller, and the reactor is destroyed once the test is done.
+pub fn run<TS, F, Fut>(name: &str, timeout: Duration, test: F) -> Fut::Output
+where
+    TS: ThreadSafety,
+    F: FnOnce(EventLoopWindowTarget<TS>) -> Fut,
+    Fut: Future,
+{
+    let _lock = TEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    IN_USE.store(true, Ordering::SeqCst);
+    let _reactor = DropReactor::<TS> {
+        name,
+        _thread_safety: PhantomData,
+    };
+
+    let mut builder = EventLoopBuilder::new();
+    #[cfg(feature = "headless")]
+    let mut event_loop = builder.build_headless::<TS>();
+    #[cfg(not(feature = "headless"))]
+    let mut event_loop = {
+        allow_any_thread(&mut builder);
+        builder.build::<TS>()
+    };
+    let target = event_loop.window_target().clone();
+
+    let watchdog = Watchdog::start(timeout);
+    let test = future::or(test(target), async {
+        watchdog.expired().await;
+        panic!("test `{name}` timed out after {timeout:?}")
+    });
+
+    match event_loop.block_on_demand(test) {
+        ReturnOrFinish::FutureReturned(output) => output,
+        ReturnOrFinish::Output(_) => panic!("the event loop exited before test `{name}` finished"),
+    }
+}
+
+/// Let the event loop run on the test's thread.
+#[cfg(not(feature = "headless"))]
+fn allow_any_thread(builder: &mut EventLoopBuilder) {
+    cfg_if::cfg_if! {
+        if #[cfg(x11_platform)] {
+            use crate::platform::x11::EventLoopBuilderExtX11;
+            builder.with_any_thread(true);
+        } else if #[cfg(wayland_platform)] {
+            use crate::platform::wayland::EventLoopBuilderExtWayland;
+            builder.with_any_thread(true);
+        } else if #[cfg(windows)] {
+            use crate::platform::windows::EventLoopBuilderExtWindows;
+            builder.with_any_thread(true);
+        } else {
+            let _ = builder;
+        }
+    }
+}
+
+/// Destroys the reactor once the test is done, even if it panicked.
+struct DropReactor<'a, TS: ThreadSafety> {
+    /// The name of the test.
+    name: &'a str,
+
+    _thread_safety: PhantomData<TS>,
+}
+
+impl<TS: ThreadSafety> Drop for DropReactor<'_, TS> {
+    fn drop(&mut self) {
+        // A `Window`, `Timer` or task that outlives the test keeps the reactor alive. Later tests
+        // have to reuse it, and can't run on another thread if it isn't thread-safe. Only blame
+        // this test for the handles that it added.
+        let leaked = TS::drop_reactor();
+        let earlier = LEAKED.swap(leaked, Ordering::SeqCst);
+        if leaked <= earlier {
+            return;
+        }
+
+        let message = format!(
+            "test `{}` leaked {} handle(s) to the reactor; drop every `Window`, `Timer` and task \
+             that uses the event loop before the test returns",
+            self.name,
+            leaked - earlier
+        );
+
+        if thread::panicking() {
+            eprintln!("{message}");
+        } else {
+            panic!("{message}");
+        }
+    }
+}
+
+/// Wakes up the test once its timeout has elapsed.
+///
+/// This uses the system clock, since the reactor's clock may be a `ManualClock`.
+struct Watchdog {
+    /// The time at which the test times out.
+    deadline: Instant,
+
+    /// The waker to wake up once the timeout has elapsed.
+    waker: Arc<Mutex<Option<Waker>>>,
+
+    /// Stops the watchdog thread once dropped.
+    _stop: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    fn start(timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let (stop, stopped) = mpsc::channel();
+
+        thread::spawn({
+            let waker = waker.clone();
+            move || {
+                if let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout) {
+                    let waker = waker.lock().unwrap_or_else(PoisonError::into_inner).take();
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+        });
+
+        Self {
+            deadline,
+            waker,
+            _stop: stop,
+        }
+    }
+
+    /// Wait for the timeout to elapse.
+    async fn expired(&self) {
+        future::poll_fn(|cx| {
+            // Register the waker before checking, so that the watchdog can't be missed.
+            *self.waker.lock().unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+
+            if Instant::now() >= self.deadline {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// Storage for the thread-safe reactor while tests are run.
+#[cfg(feature = "thread_safe")]
+pub(crate) mod thread_safe {
+    use super::IN_USE;
+
+    use crate::reactor::Reactor;
+    use crate::sync::ThreadSafe;
+
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, PoisonError, RwLock};
+
+    /// The thread-safe reactor for the current test.
+    static REACTOR: RwLock<Option<Arc<Reactor<ThreadSafe>>>> = RwLock::new(None);
+
+    /// Get the reactor for the current test, creating it if needed.
+    ///
+    /// Returns `None` if no test has been run, in which case the global reactor is used.
+    pub(crate) fn get_reactor() -> Option<Arc<Reactor<ThreadSafe>>> {
+        if !IN_USE.load(Ordering::Acquire) {
+            return None;
+        }
+
+        if let Some(reactor) = &*REACTOR.read().unwrap_or_else(PoisonError::into_inner) {
+            return Some(reactor.clone());
+        }
+
+        let reactor = REACTOR
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(|| Arc::new(Reactor::new()))
+            .clone();
+        Some(reactor)
+    }
+
+    /// Get the reactor for the current test if it exists, without creating it.
+    ///
+    /// Returns `None` if no test has been run, in which case the global reactor is used.
+    pub(crate) fn try_get_reactor() -> Option<Option<Arc<Reactor<ThreadSafe>>>> {
+        if !IN_USE.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let reactor = REACTOR.read().unwrap_or_else(PoisonError::into_inner);
+        Some(reactor.clone())
+    }
+
+    /// Destroy the reactor for the current test, unless other handles to it are still alive.
+    ///
+    /// Returns the number of other handles.
+    pub(crate) fn drop_reactor() -> usize {
+        let reactor = {
+            let mut reactor = REACTOR.write().unwrap_or_else(PoisonError::into_inner);
+            match &*reactor {
+                Some(handle) if Arc::strong_count(handle) > 1 => {
+                    return Arc::strong_count(handle) - 1
+                }
+                _ => reactor.take(),
+            }
+        };
+
+        // Destroy the reactor without holding the lock.
+        drop(reactor);
+        0
+    }
+}
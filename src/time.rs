@@ -0,0 +1,160 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Utilities for putting time limits on futures.
+
+use crate::sync::ThreadSafety;
+use crate::{DefaultThreadSafety, Timer};
+
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use pin_project_lite::pin_project;
+
+/// Run a future, giving up if it doesn't complete within `duration`.
+///
+/// The time limit is measured with a [`Timer`] on the reactor's clock. Use [`Timeout::new`] to put
+/// a time limit on a future with a timer of another [`ThreadSafety`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use async_winit::time::timeout;
+/// use std::time::Duration;
+///
+/// # async fn confirm() -> bool { true }
+/// # async fn example() {
+/// match timeout(Duration::from_millis(500), confirm()).await {
+///     Ok(confirmed) => println!("confirmed: {confirmed}"),
+///     Err(_) => println!("no answer in time"),
+/// }
+/// # }
+/// ```
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout::new(future, Timer::after(duration))
+}
+
+/// Run a future, giving up if it doesn't complete by `deadline`.
+///
+/// The deadline is measured with a [`Timer`] on the reactor's clock. Use [`Timeout::new`] to put a
+/// deadline on a future with a timer of another [`ThreadSafety`].
+pub fn deadline<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout::new(future, Timer::at(deadline))
+}
+
+pin_project! {
+    /// A future with a time limit.
+    ///
+    /// This is returned by [`timeout`], [`deadline`], [`Handler::wait_timeout`] and
+    /// [`Waiter::next_within`]. It resolves to the output of the inner future, or to [`Elapsed`]
+    /// if the time limit is reached first.
+    ///
+    /// [`Handler::wait_timeout`]: crate::Handler::wait_timeout
+    /// [`Waiter::next_within`]: crate::Waiter::next_within
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct Timeout<F, TS: ThreadSafety = DefaultThreadSafety> {
+        #[pin]
+        future: F,
+        timer: Timer<TS>,
+    }
+}
+
+impl<F, TS: ThreadSafety> Timeout<F, TS> {
+    /// Put a time limit on a future, which is reached once `timer` fires.
+    ///
+    /// This works with timers of any [`ThreadSafety`], unlike [`timeout`] and [`deadline`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use async_winit::time::Timeout;
+    /// use async_winit::{ThreadUnsafe, Timer};
+    /// use std::time::Duration;
+    ///
+    /// # async fn confirm() -> bool { true }
+    /// # async fn example() {
+    /// let timer = Timer::<ThreadUnsafe>::after(Duration::from_millis(500));
+    /// match Timeout::new(confirm(), timer).await {
+    ///     Ok(confirmed) => println!("confirmed: {confirmed}"),
+    ///     Err(_) => println!("no answer in time"),
+    /// }
+    /// # }
+    /// ```
+    pub fn new(future: F, timer: Timer<TS>) -> Self {
+        Self { future, timer }
+    }
+
+    /// Get a reference to the inner future.
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    /// Get a mutable reference to the inner future.
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.future
+    }
+
+    /// Take the inner future out, without a time limit.
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F, TS: ThreadSafety> fmt::Debug for Timeout<F, TS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("timer", &self.timer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Future, TS: ThreadSafety> Future for Timeout<F, TS> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        // Give the future a chance to complete, even if the time limit was reached.
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(this.timer).poll(cx) {
+            Poll::Ready(_) => Poll::Ready(Err(Elapsed { _private: () })),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// The error returned when a [`Timeout`] reaches its time limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Elapsed {
+    _private: (),
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
@@ -37,10 +37,16 @@ use futures_lite::stream::Stream;
 /// This timer waits for a specific duration or interval to elapse before returning `Poll::Ready`.
 /// It uses the [`ControlFlow::WaitUntil`] mechanism to wait for the timer to fire.
 ///
-/// This type is similar to the [`Timer`] type in the `async-io` crate. The main practical difference
-/// is that, on certain platforms, this `Timer` type may have marginally higher precision.
+/// Time is measured by the reactor's [`Clock`], which is the system clock unless another clock was
+/// installed with [`EventLoopBuilder::with_clock`]. Timers have a resolution of one millisecond.
+/// A timer can be given a tolerance with [`Timer::with_tolerance`], which allows it to fire late
+/// so that it can share a wakeup with other timers.
+///
+/// This type is similar to the [`Timer`] type in the `async-io` crate.
 ///
 /// [`ControlFlow::WaitUntil`]: crate::event_loop::ControlFlow::WaitUntil
+/// [`Clock`]: crate::Clock
+/// [`EventLoopBuilder::with_clock`]: crate::event_loop::EventLoopBuilder::with_clock
 /// [`Timer`]: https://docs.rs/async-io/latest/async_io/timer/struct.Timer.html
 pub struct Timer<TS: ThreadSafety = crate::DefaultThreadSafety> {
     /// Static reference to the reactor.
@@ -54,6 +60,71 @@ pub struct Timer<TS: ThreadSafety = crate::DefaultThreadSafety> {
 
     /// The period.
     period: Duration,
+
+    /// How late this timer is allowed to fire.
+    tolerance: Duration,
+
+    /// What to do when this timer misses a tick.
+    missed_tick_behavior: MissedTickBehavior,
+
+    /// Whether this timer is paused while the application is suspended.
+    pausable: bool,
+
+    /// The reactor's total suspended time and number of suspensions when the deadline was last
+    /// set.
+    suspended_base: (Duration, u64),
+}
+
+/// How an interval [`Timer`] behaves when it misses a tick.
+///
+/// A tick is missed when the timer is polled after the deadline of the tick following the one
+/// that is being reported. This happens when the event loop stalls, for instance during a modal
+/// resize on some platforms or a long frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MissedTickBehavior {
+    /// Fire the missed ticks as fast as possible until the timer has caught up.
+    ///
+    /// The timer keeps firing at the deadlines it was originally scheduled for.
+    #[default]
+    Burst,
+
+    /// Schedule the next tick one period after the time at which the late tick fired.
+    ///
+    /// This shifts all future deadlines of the timer.
+    Delay,
+
+    /// Skip the missed ticks and fire at the next deadline that is still in the future.
+    ///
+    /// The timer stays aligned to the deadlines it was originally scheduled for.
+    Skip,
+}
+
+impl MissedTickBehavior {
+    /// Get the deadline of the tick after the one at `deadline`, which fired at `now`.
+    fn next_deadline(self, deadline: Instant, period: Duration, now: Instant) -> Option<Instant> {
+        let next = deadline.checked_add(period)?;
+        if next > now {
+            // No ticks were missed.
+            return Some(next);
+        }
+
+        match self {
+            Self::Burst => Some(next),
+            Self::Delay => now.checked_add(period),
+            Self::Skip => {
+                if period.is_zero() {
+                    return Some(now);
+                }
+
+                // Find the first tick after `now`. This only needs how far `now` is past the last
+                // missed tick, so it doesn't overflow however many ticks were missed.
+                let late = (now - deadline).as_nanos() % period.as_nanos();
+                let late =
+                    Duration::new((late / 1_000_000_000) as u64, (late % 1_000_000_000) as u32);
+                now.checked_add(period - late)
+            }
+        }
+    }
 }
 
 impl<TS: ThreadSafety> fmt::Debug for Timer<TS> {
@@ -61,6 +132,9 @@ impl<TS: ThreadSafety> fmt::Debug for Timer<TS> {
         f.debug_struct("Timer")
             .field("deadline", &self.deadline)
             .field("period", &self.period)
+            .field("tolerance", &self.tolerance)
+            .field("missed_tick_behavior", &self.missed_tick_behavior)
+            .field("pausable", &self.pausable)
             .field("registered", &self.id_and_waker.is_some())
             .finish()
     }
@@ -76,6 +150,10 @@ impl<TS: ThreadSafety> Timer<TS> {
             id_and_waker: None,
             deadline: None,
             period: Duration::MAX,
+            tolerance: Duration::ZERO,
+            missed_tick_behavior: MissedTickBehavior::Burst,
+            pausable: false,
+            suspended_base: (Duration::ZERO, 0),
         }
     }
 
@@ -86,7 +164,8 @@ impl<TS: ThreadSafety> Timer<TS> {
 
     /// Create a timer that fires after the given duration.
     pub fn after(duration: Duration) -> Self {
-        Instant::now()
+        Reactor::<TS>::get()
+            .now()
             .checked_add(duration)
             .map_or_else(Self::never, Self::at)
     }
@@ -98,7 +177,8 @@ impl<TS: ThreadSafety> Timer<TS> {
 
     /// Create a timer that fires on an interval.
     pub fn interval(period: Duration) -> Self {
-        Instant::now()
+        Reactor::<TS>::get()
+            .now()
             .checked_add(period)
             .map_or_else(Self::never, |deadline| Self::interval_at(deadline, period))
     }
@@ -110,6 +190,92 @@ impl<TS: ThreadSafety> Timer<TS> {
             id_and_waker: None,
             deadline: Some(start),
             period,
+            tolerance: Duration::ZERO,
+            missed_tick_behavior: MissedTickBehavior::Burst,
+            pausable: false,
+            suspended_base: (Duration::ZERO, 0),
+        }
+    }
+
+    /// Allow this timer to fire up to `tolerance` after its deadline.
+    ///
+    /// The reactor uses this slack to coalesce timers with nearby deadlines into a single
+    /// wakeup of the event loop, which saves power for periodic work that does not need to run
+    /// at an exact time.
+    pub fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.set_tolerance(tolerance);
+        self
+    }
+
+    /// Set how late this timer is allowed to fire.
+    ///
+    /// See [`Timer::with_tolerance`] for more information.
+    pub fn set_tolerance(&mut self, tolerance: Duration) {
+        self.tolerance = tolerance;
+        self.reregister();
+    }
+
+    /// Get how this timer behaves when it misses a tick.
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    /// Set how this timer behaves when it misses a tick.
+    ///
+    /// This only affects interval timers. The default is [`MissedTickBehavior::Burst`].
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+
+    /// Pause this timer while the application is suspended.
+    ///
+    /// When the application is [`suspended`], the time remaining until the timer fires is frozen,
+    /// and the timer doesn't wake up the event loop. Once the application is [`resumed`], the timer
+    /// picks up where it left off. This is useful for poll loops, heartbeats and animations that
+    /// have no reason to run while the application's surfaces are gone.
+    ///
+    /// [`suspended`]: crate::event_loop::EventLoopWindowTarget::suspended
+    /// [`resumed`]: crate::event_loop::EventLoopWindowTarget::resumed
+    pub fn pause_while_suspended(mut self) -> Self {
+        self.set_pause_while_suspended(true);
+        self
+    }
+
+    /// Set whether this timer is paused while the application is suspended.
+    ///
+    /// See [`Timer::pause_while_suspended`] for more information.
+    pub fn set_pause_while_suspended(&mut self, pause: bool) {
+        if self.pausable == pause {
+            return;
+        }
+
+        self.pausable = pause;
+        if pause {
+            self.suspended_base = self.reactor.suspended_time();
+        }
+        self.reregister();
+    }
+
+    /// Restart the period of this interval timer from now.
+    ///
+    /// The next tick fires one period from now. Unlike the `set_*` methods, this keeps the timer
+    /// registered with the reactor and keeps its tolerance and missed tick behavior.
+    ///
+    /// If this timer is not an interval, this does nothing.
+    pub fn reset(&mut self) {
+        if self.deadline.is_none() || self.period == Duration::MAX {
+            return;
+        }
+
+        match self.reactor.now().checked_add(self.period) {
+            Some(deadline) => {
+                self.deadline = Some(deadline);
+                if self.pausable {
+                    self.suspended_base = self.reactor.suspended_time();
+                }
+                self.reregister();
+            }
+            None => self.set_never(),
         }
     }
 
@@ -121,7 +287,7 @@ impl<TS: ThreadSafety> Timer<TS> {
 
     /// Set this timer to fire after the given duration.
     pub fn set_after(&mut self, duration: Duration) {
-        match Instant::now().checked_add(duration) {
+        match self.reactor.now().checked_add(duration) {
             Some(deadline) => self.set_at(deadline),
             None => self.set_never(),
         }
@@ -134,7 +300,7 @@ impl<TS: ThreadSafety> Timer<TS> {
 
     /// Set this timer to run at an interval.
     pub fn set_interval(&mut self, period: Duration) {
-        match Instant::now().checked_add(period) {
+        match self.reactor.now().checked_add(period) {
             Some(deadline) => self.set_interval_at(deadline, period),
             None => self.set_never(),
         }
@@ -146,16 +312,54 @@ impl<TS: ThreadSafety> Timer<TS> {
 
         self.deadline = Some(start);
         self.period = period;
+        if self.pausable {
+            self.suspended_base = self.reactor.suspended_time();
+        }
 
         if let Some((id, waker)) = self.id_and_waker.as_mut() {
             // Re-register the timer into the reactor.
-            *id = self.reactor.insert_timer(start, waker);
+            *id = self
+                .reactor
+                .insert_timer(start, self.tolerance, self.pausable, waker);
+        }
+    }
+
+    /// Re-register this timer into the reactor with its current deadline.
+    fn reregister(&mut self) {
+        if let (Some(deadline), Some((id, waker))) = (self.deadline, self.id_and_waker.as_mut()) {
+            self.reactor.remove_timer(*id);
+            *id = self
+                .reactor
+                .insert_timer(deadline, self.tolerance, self.pausable, waker);
+        }
+    }
+
+    /// Push the deadline back by the time spent suspended since it was set.
+    fn catch_up_with_suspension(&mut self) {
+        let suspended = self.reactor.suspended_time();
+        if suspended.1 == self.suspended_base.1 {
+            return;
+        }
+
+        // The reactor took the timer out of the wheel when the application was suspended, so it
+        // has to be registered again even if no time passed.
+        let shift = suspended.0.saturating_sub(self.suspended_base.0);
+        self.suspended_base = suspended;
+        match self
+            .deadline
+            .and_then(|deadline| deadline.checked_add(shift))
+        {
+            Some(deadline) => {
+                self.deadline = Some(deadline);
+                self.reregister();
+            }
+            None => self.set_never(),
         }
     }
 
     fn clear(&mut self) {
-        if let (Some(deadline), Some((id, _))) = (self.deadline.take(), self.id_and_waker.take()) {
-            self.reactor.remove_timer(deadline, id);
+        if let (Some(_), Some((id, _))) = (self.deadline.take(), self.id_and_waker.take()) {
+            self.reactor.remove_timer(id);
         }
     }
 }
@@ -180,20 +384,41 @@ impl<TS: ThreadSafety> Stream for Timer<TS> {
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
 
+        if this.pausable && this.deadline.is_some() {
+            if this.reactor.is_suspended() {
+                // Stay out of the timer wheel until the application is resumed.
+                if let Some((id, _)) = this.id_and_waker.take() {
+                    this.reactor.remove_timer(id);
+                }
+
+                this.reactor.wake_on_resume(cx.waker());
+                return Poll::Pending;
+            }
+
+            this.catch_up_with_suspension();
+        }
+
         if let Some(ref mut deadline) = this.deadline {
             // Check if the timer is ready.
-            if *deadline < Instant::now() {
+            let now = this.reactor.now();
+            if *deadline <= now {
                 if let Some((id, _)) = this.id_and_waker.take() {
-                    this.reactor.remove_timer(*deadline, id);
+                    this.reactor.remove_timer(id);
                 }
 
                 let result_time = *deadline;
 
-                if let Some(next) = deadline.checked_add(this.period) {
+                let next = this
+                    .missed_tick_behavior
+                    .next_deadline(*deadline, this.period, now);
+
+                if let Some(next) = next {
                     *deadline = next;
 
                     // Register the timer into the reactor.
-                    let id = this.reactor.insert_timer(next, cx.waker());
+                    let id =
+                        this.reactor
+                            .insert_timer(next, this.tolerance, this.pausable, cx.waker());
                     this.id_and_waker = Some((id, cx.waker().clone()));
                 } else {
                     this.deadline = None;
@@ -205,16 +430,26 @@ impl<TS: ThreadSafety> Stream for Timer<TS> {
                 match &this.id_and_waker {
                     None => {
                         // This timer needs to be registered.
-                        let id = this.reactor.insert_timer(*deadline, cx.waker());
+                        let id = this.reactor.insert_timer(
+                            *deadline,
+                            this.tolerance,
+                            this.pausable,
+                            cx.waker(),
+                        );
                         this.id_and_waker = Some((id, cx.waker().clone()));
                     }
 
                     Some((id, w)) if !w.will_wake(cx.waker()) => {
                         // Deregister timer and remove the old waker.
-                        this.reactor.remove_timer(*deadline, *id);
+                        this.reactor.remove_timer(*id);
 
                         // Register the timer into the reactor.
-                        let id = this.reactor.insert_timer(*deadline, cx.waker());
+                        let id = this.reactor.insert_timer(
+                            *deadline,
+                            this.tolerance,
+                            this.pausable,
+                            cx.waker(),
+                        );
                         this.id_and_waker = Some((id, cx.waker().clone()));
                     }
 
@@ -28,13 +28,23 @@ use crate::oneoff::oneoff;
 use crate::reactor::{EventLoopOp, Reactor};
 use crate::sync::{ThreadSafety, __private::Rc};
 
+pub(crate) mod batches;
+mod frames;
+mod raw;
 pub(crate) mod registration;
+#[cfg(feature = "serde")]
+pub(crate) mod surrogates;
+mod visibility;
 
-use registration::Registration;
+pub use batches::InputBatches;
+pub use frames::{Frame, FrameStats, Frames};
+pub(crate) use raw::RawWindow;
+use registration::{keys, Registration};
+pub use visibility::{VisibilityState, VisibilityWatch};
 
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::error::{ExternalError, NotSupportedError};
-use winit::event::DeviceId;
+use winit::event::{DeviceId, WindowEvent};
 use winit::monitor::MonitorHandle;
 
 #[doc(inline)]
@@ -44,24 +54,35 @@ pub use winit::window::{
 };
 
 /// Attributes to use when creating a window.
+///
+/// With the `serde` feature, these can be serialized and deserialized, and fields that are missing
+/// take their default values. `enabled_buttons` is an object with `close`, `minimize` and
+/// `maximize` flags, `window_level` is the name of the level, and `fullscreen` is `true` for
+/// borderless fullscreen on the current monitor. `window_icon` is skipped.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct WindowAttributes {
     pub inner_size: Option<Size>,
     pub min_inner_size: Option<Size>,
     pub max_inner_size: Option<Size>,
     pub position: Option<Position>,
     pub resizable: bool,
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::window_buttons"))]
     pub enabled_buttons: WindowButtons,
     pub title: String,
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::fullscreen"))]
     pub fullscreen: Option<Fullscreen>,
     pub maximized: bool,
     pub visible: bool,
     pub transparent: bool,
     pub decorations: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub window_icon: Option<Icon>,
     pub preferred_theme: Option<Theme>,
     pub resize_increments: Option<Size>,
     pub content_protected: bool,
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::window_level"))]
     pub window_level: WindowLevel,
     pub active: bool,
 }
@@ -380,13 +401,13 @@ impl WindowBuilder {
             })
             .await;
 
-        let inner = rx.recv().await?;
+        let inner = TS::Rc::new(rx.recv().await?);
 
         // Insert the window into the global window map.
-        let registration = reactor.insert_window(inner.id());
+        let registration = reactor.insert_window(&inner);
 
         Ok(Window {
-            inner: TS::Rc::new(inner),
+            inner,
             registration,
             reactor,
         })
@@ -446,11 +467,259 @@ impl WindowBuilder {
     }
 }
 
+/// A batch of changes to apply to a window at once.
+///
+/// This is passed to the closure given to [`Window::update`]. Every property that is set here is
+/// applied to the window in a single pass of the event loop, so intermediate states are never
+/// shown on screen.
+///
+/// Regardless of the order in which they were set, the changes are applied in the following order:
+///
+/// 1. Size constraints: minimum and maximum inner size, then resize increments.
+/// 2. The inner size and the outer position.
+/// 3. Window decorations and properties: resizability, enabled buttons, decorations, title, icon,
+///    theme, window level, content protection and transparency.
+/// 4. Fullscreen and maximization.
+/// 5. Visibility.
+///
+/// This way, a hidden window can be fully configured before it is shown.
+#[derive(Debug, Clone, Default)]
+pub struct WindowUpdate {
+    /// The new values for the window's properties.
+    attributes: WindowAttributes,
+
+    /// Bitmask of the `attributes` that were set.
+    changed: u32,
+}
+
+impl WindowUpdate {
+    const INNER_SIZE: u32 = 1 << 0;
+    const MIN_INNER_SIZE: u32 = 1 << 1;
+    const MAX_INNER_SIZE: u32 = 1 << 2;
+    const POSITION: u32 = 1 << 3;
+    const RESIZABLE: u32 = 1 << 4;
+    const ENABLED_BUTTONS: u32 = 1 << 5;
+    const TITLE: u32 = 1 << 6;
+    const FULLSCREEN: u32 = 1 << 7;
+    const MAXIMIZED: u32 = 1 << 8;
+    const VISIBLE: u32 = 1 << 9;
+    const TRANSPARENT: u32 = 1 << 10;
+    const DECORATIONS: u32 = 1 << 11;
+    const WINDOW_ICON: u32 = 1 << 12;
+    const THEME: u32 = 1 << 13;
+    const RESIZE_INCREMENTS: u32 = 1 << 14;
+    const CONTENT_PROTECTED: u32 = 1 << 15;
+    const WINDOW_LEVEL: u32 = 1 << 16;
+
+    /// Create an empty update.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tell whether no changes have been made.
+    pub fn is_empty(&self) -> bool {
+        self.changed == 0
+    }
+
+    /// Request a new inner size for the window.
+    ///
+    /// See [`Window::request_inner_size`] for details.
+    pub fn set_inner_size(&mut self, size: impl Into<Size>) -> &mut Self {
+        self.attributes.inner_size = Some(size.into());
+        self.changed |= Self::INNER_SIZE;
+        self
+    }
+
+    /// Set the minimum inner size of the window.
+    pub fn set_min_inner_size(&mut self, size: impl Into<Option<Size>>) -> &mut Self {
+        self.attributes.min_inner_size = size.into();
+        self.changed |= Self::MIN_INNER_SIZE;
+        self
+    }
+
+    /// Set the maximum inner size of the window.
+    pub fn set_max_inner_size(&mut self, size: impl Into<Option<Size>>) -> &mut Self {
+        self.attributes.max_inner_size = size.into();
+        self.changed |= Self::MAX_INNER_SIZE;
+        self
+    }
+
+    /// Set the outer position of the window.
+    pub fn set_outer_position(&mut self, position: impl Into<Position>) -> &mut Self {
+        self.attributes.position = Some(position.into());
+        self.changed |= Self::POSITION;
+        self
+    }
+
+    /// Set whether the window is resizable.
+    pub fn set_resizable(&mut self, resizable: bool) -> &mut Self {
+        self.attributes.resizable = resizable;
+        self.changed |= Self::RESIZABLE;
+        self
+    }
+
+    /// Set the enabled window buttons.
+    pub fn set_enabled_buttons(&mut self, buttons: WindowButtons) -> &mut Self {
+        self.attributes.enabled_buttons = buttons;
+        self.changed |= Self::ENABLED_BUTTONS;
+        self
+    }
+
+    /// Set the title of the window.
+    pub fn set_title(&mut self, title: impl Into<String>) -> &mut Self {
+        self.attributes.title = title.into();
+        self.changed |= Self::TITLE;
+        self
+    }
+
+    /// Set the window's fullscreen state.
+    pub fn set_fullscreen(&mut self, fullscreen: Option<Fullscreen>) -> &mut Self {
+        self.attributes.fullscreen = fullscreen;
+        self.changed |= Self::FULLSCREEN;
+        self
+    }
+
+    /// Set the window's maximization.
+    pub fn set_maximized(&mut self, maximized: bool) -> &mut Self {
+        self.attributes.maximized = maximized;
+        self.changed |= Self::MAXIMIZED;
+        self
+    }
+
+    /// Set whether the window is visible.
+    pub fn set_visible(&mut self, visible: bool) -> &mut Self {
+        self.attributes.visible = visible;
+        self.changed |= Self::VISIBLE;
+        self
+    }
+
+    /// Set the window's transparency.
+    pub fn set_transparent(&mut self, transparent: bool) -> &mut Self {
+        self.attributes.transparent = transparent;
+        self.changed |= Self::TRANSPARENT;
+        self
+    }
+
+    /// Set the window's decorations.
+    pub fn set_decorations(&mut self, decorations: bool) -> &mut Self {
+        self.attributes.decorations = decorations;
+        self.changed |= Self::DECORATIONS;
+        self
+    }
+
+    /// Set the window icon.
+    pub fn set_window_icon(&mut self, icon: Option<Icon>) -> &mut Self {
+        self.attributes.window_icon = icon;
+        self.changed |= Self::WINDOW_ICON;
+        self
+    }
+
+    /// Set the window's theme.
+    pub fn set_theme(&mut self, theme: Option<Theme>) -> &mut Self {
+        self.attributes.preferred_theme = theme;
+        self.changed |= Self::THEME;
+        self
+    }
+
+    /// Set the resize increments of the window.
+    pub fn set_resize_increments(&mut self, size: impl Into<Option<Size>>) -> &mut Self {
+        self.attributes.resize_increments = size.into();
+        self.changed |= Self::RESIZE_INCREMENTS;
+        self
+    }
+
+    /// Set the window's protected content.
+    pub fn set_content_protected(&mut self, protected: bool) -> &mut Self {
+        self.attributes.content_protected = protected;
+        self.changed |= Self::CONTENT_PROTECTED;
+        self
+    }
+
+    /// Set the window level.
+    pub fn set_window_level(&mut self, level: WindowLevel) -> &mut Self {
+        self.attributes.window_level = level;
+        self.changed |= Self::WINDOW_LEVEL;
+        self
+    }
+
+    /// Apply the changes to a window.
+    pub(crate) fn apply(self, window: &RawWindow) {
+        let Self {
+            attributes: attrs,
+            changed,
+        } = self;
+        let has = |flag: u32| changed & flag != 0;
+
+        if has(Self::MIN_INNER_SIZE) {
+            window.set_min_inner_size(attrs.min_inner_size);
+        }
+        if has(Self::MAX_INNER_SIZE) {
+            window.set_max_inner_size(attrs.max_inner_size);
+        }
+        if has(Self::RESIZE_INCREMENTS) {
+            window.set_resize_increments(attrs.resize_increments);
+        }
+
+        if let (true, Some(size)) = (has(Self::INNER_SIZE), attrs.inner_size) {
+            let _ = window.request_inner_size(size);
+        }
+        if let (true, Some(position)) = (has(Self::POSITION), attrs.position) {
+            window.set_outer_position(position);
+        }
+
+        if has(Self::RESIZABLE) {
+            window.set_resizable(attrs.resizable);
+        }
+        if has(Self::ENABLED_BUTTONS) {
+            window.set_enabled_buttons(attrs.enabled_buttons);
+        }
+        if has(Self::DECORATIONS) {
+            window.set_decorations(attrs.decorations);
+        }
+        if has(Self::TITLE) {
+            window.set_title(&attrs.title);
+        }
+        if has(Self::WINDOW_ICON) {
+            window.set_window_icon(attrs.window_icon);
+        }
+        if has(Self::THEME) {
+            window.set_theme(attrs.preferred_theme);
+        }
+        if has(Self::WINDOW_LEVEL) {
+            window.set_window_level(attrs.window_level);
+        }
+        if has(Self::CONTENT_PROTECTED) {
+            window.set_content_protected(attrs.content_protected);
+        }
+        if has(Self::TRANSPARENT) {
+            window.set_transparent(attrs.transparent);
+        }
+
+        if has(Self::FULLSCREEN) {
+            window.set_fullscreen(attrs.fullscreen);
+        }
+        if has(Self::MAXIMIZED) {
+            window.set_maximized(attrs.maximized);
+        }
+
+        if has(Self::VISIBLE) {
+            window.set_visible(attrs.visible);
+        }
+    }
+}
+
 /// A window.
+///
+/// # Panics
+///
+/// Methods that have to run on the event loop panic with a [`LoopClosed`] payload if the event
+/// loop is destroyed before they complete.
+///
+/// [`LoopClosed`]: crate::event_loop::LoopClosed
 #[derive(Clone)]
 pub struct Window<TS: ThreadSafety> {
     /// Underlying window.
-    inner: TS::Rc<winit::window::Window>,
+    inner: TS::Rc<RawWindow>,
 
     /// Registration for the window.
     registration: TS::Rc<Registration<TS>>,
@@ -461,9 +730,10 @@ pub struct Window<TS: ThreadSafety> {
 
 impl<TS: ThreadSafety> Drop for Window<TS> {
     fn drop(&mut self) {
-        if let Some(inner) = TS::Rc::get_mut(&mut self.inner) {
+        // The registration only holds a weak reference to the window, so count strong ones.
+        if TS::Rc::strong_count(&self.inner) == 1 {
             println!("Dropping a window");
-            self.reactor.remove_window(inner.id());
+            self.reactor.remove_window(self.inner.id());
         }
         else {
             println!("Not dropping window {}", TS::Rc::strong_count(&self.inner))
@@ -473,13 +743,13 @@ impl<TS: ThreadSafety> Drop for Window<TS> {
 
 unsafe impl<TS: ThreadSafety> raw_window_handle::HasRawDisplayHandle for Window<TS> {
     fn raw_display_handle(&self) -> raw_window_handle::RawDisplayHandle {
-        self.inner.raw_display_handle()
+        self.inner.winit().raw_display_handle()
     }
 }
 
 unsafe impl<TS: ThreadSafety> raw_window_handle::HasRawWindowHandle for Window<TS> {
     fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
-        self.inner.raw_window_handle()
+        self.inner.winit().raw_window_handle()
     }
 }
 
@@ -489,6 +759,19 @@ impl<TS: ThreadSafety> Window<TS> {
         WindowBuilder::new().build().await
     }
 
+    /// Get a handle to a window from its registration, if the window still exists.
+    #[cfg(all(feature = "automation", unix))]
+    pub(crate) fn from_registration(
+        reactor: TS::Rc<Reactor<TS>>,
+        registration: TS::Rc<Registration<TS>>,
+    ) -> Option<Self> {
+        Some(Self {
+            inner: registration.window()?,
+            registration,
+            reactor,
+        })
+    }
+
     /// Gets whether the window has keyboard focus.
     ///
     /// This queries the same state information as [`WindowEvent::Focused`].
@@ -536,12 +819,25 @@ impl<TS: ThreadSafety> Window<TS> {
     #[inline]
     #[must_use]
     pub fn request_inner_size<S: Into<Size>>(&self, size: S) -> Option<PhysicalSize<u32>> {
-        self.inner.request_inner_size(size)
+        self.inner.request_inner_size(size.into())
     }
 
     /// Get a reference to the underlying window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window was created by a headless event loop. Use `Window::headless_window`
+    /// to get those.
     pub fn window(&self) -> &winit::window::Window {
-        &self.inner
+        self.inner.winit()
+    }
+
+    /// Get a reference to the headless window that backs this window.
+    ///
+    /// Returns `None` if the window wasn't created by a headless event loop.
+    #[cfg(feature = "headless")]
+    pub fn headless_window(&self) -> Option<&crate::headless::HeadlessWindow> {
+        self.inner.headless()
     }
 
     /// Get the ID of the window.
@@ -561,8 +857,65 @@ impl<TS: ThreadSafety> Window<TS> {
 }
 
 impl<TS: ThreadSafety> Window<TS> {
+    /// Inject a synthetic event, as if it came from the event loop.
+    ///
+    /// The event goes through the interceptors and is delivered to this window's handlers the
+    /// same way that events from the event loop are, and this waits until it has been
+    /// dispatched. Keyboard input is marked with `is_synthetic`. This can be called from any
+    /// thread.
+    ///
+    /// `winit` doesn't allow creating key events, so use [`Window::inject_key`] to inject
+    /// keyboard input.
+    pub async fn inject(&self, mut event: WindowEvent) {
+        if let WindowEvent::KeyboardInput { is_synthetic, .. } = &mut event {
+            *is_synthetic = true;
+        }
+
+        #[cfg(feature = "headless")]
+        if let Some(window) = self.inner.headless() {
+            window.observe(&event);
+        }
+
+        self.reactor
+            .inject(winit::event::Event::WindowEvent {
+                window_id: self.inner.id(),
+                event,
+            })
+            .await;
+    }
+
+    /// Inject a synthetic key press or release.
+    ///
+    /// The event is delivered to [`Window::keyboard_input`] and to
+    /// [`EventLoopWindowTarget::any_keyboard_input`] with `is_synthetic` set and a placeholder
+    /// device ID, and this waits until it has been dispatched. Unlike events injected with
+    /// [`Window::inject`], it doesn't go through the interceptors and isn't part of any input
+    /// batch, since those only see `winit` events. This can be called from any thread.
+    ///
+    /// [`EventLoopWindowTarget::any_keyboard_input`]: crate::event_loop::EventLoopWindowTarget::any_keyboard_input
+    pub async fn inject_key(&self, event: crate::event::KeyEvent) {
+        // SAFETY: The dummy ID is only compared against other device IDs.
+        let device_id = unsafe { DeviceId::dummy() };
+
+        self.reactor
+            .inject_keyboard_input(
+                self.inner.id(),
+                &self.registration,
+                crate::event::KeyboardInput {
+                    device_id,
+                    event,
+                    is_synthetic: true,
+                },
+            )
+            .await;
+    }
+
     /// Get the inner position of the window.
     pub async fn inner_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.inner_position();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::InnerPosition {
@@ -576,6 +929,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the outer position of the window.
     pub async fn outer_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.outer_position();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::OuterPosition {
@@ -589,6 +946,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the outer position of the window.
     pub async fn set_outer_position(&self, position: impl Into<Position>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_outer_position(position.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetOuterPosition {
@@ -603,6 +964,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the inner size of the window.
     pub async fn inner_size(&self) -> PhysicalSize<u32> {
+        if self.reactor.is_main_thread() {
+            return self.inner.inner_size();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::InnerSize {
@@ -616,6 +981,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the outer size of the window.
     pub async fn outer_size(&self) -> PhysicalSize<u32> {
+        if self.reactor.is_main_thread() {
+            return self.inner.outer_size();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::OuterSize {
@@ -629,6 +998,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the minimum inner size of the window.
     pub async fn set_min_inner_size(&self, size: impl Into<Option<Size>>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_min_inner_size(size.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetMinInnerSize {
@@ -643,6 +1016,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the maximum inner size of the window.
     pub async fn set_max_inner_size(&self, size: impl Into<Option<Size>>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_max_inner_size(size.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetMaxInnerSize {
@@ -657,6 +1034,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the resize increments of the window.
     pub async fn resize_increments(&self) -> Option<PhysicalSize<u32>> {
+        if self.reactor.is_main_thread() {
+            return self.inner.resize_increments();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::ResizeIncrements {
@@ -670,6 +1051,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the resize increments of the window.
     pub async fn set_resize_increments(&self, size: impl Into<Option<Size>>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_resize_increments(size.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetResizeIncrements {
@@ -684,11 +1069,16 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the title of the window.
     pub async fn set_title(&self, title: impl Into<String>) {
+        let title = title.into();
+        if self.reactor.is_main_thread() {
+            return self.inner.set_title(&title);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetTitle {
                 window: self.inner.clone(),
-                title: title.into(),
+                title,
                 waker: tx,
             })
             .await;
@@ -698,6 +1088,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set whether the window is visible.
     pub async fn set_visible(&self, visible: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_visible(visible);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetVisible {
@@ -712,6 +1106,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the window's visibility.
     pub async fn is_visible(&self) -> Option<bool> {
+        if self.reactor.is_main_thread() {
+            return self.inner.is_visible();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Visible {
@@ -725,6 +1123,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's transparency.
     pub async fn set_transparent(&self, transparent: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_transparent(transparent);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetTransparent {
@@ -739,6 +1141,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's resizable property.
     pub async fn set_resizable(&self, resizable: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_resizable(resizable);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetResizable {
@@ -753,6 +1159,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the window's resizable property.
     pub async fn is_resizable(&self) -> bool {
+        if self.reactor.is_main_thread() {
+            return self.inner.is_resizable();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Resizable {
@@ -786,33 +1196,52 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's minimization.
     pub async fn set_minimized(&self, minimized: bool) {
-        let (tx, rx) = oneoff();
-        self.reactor
-            .push_event_loop_op(EventLoopOp::SetMinimized {
-                window: self.inner.clone(),
-                minimized,
-                waker: tx,
-            })
-            .await;
+        if self.reactor.is_main_thread() {
+            self.inner.set_minimized(minimized);
+        } else {
+            let (tx, rx) = oneoff();
+            self.reactor
+                .push_event_loop_op(EventLoopOp::SetMinimized {
+                    window: self.inner.clone(),
+                    minimized,
+                    waker: tx,
+                })
+                .await;
+
+            rx.recv().await;
+        }
 
-        rx.recv().await
+        self.registration.set_minimized(minimized);
     }
 
     /// Get the window's minimization.
     pub async fn is_minimized(&self) -> Option<bool> {
-        let (tx, rx) = oneoff();
-        self.reactor
-            .push_event_loop_op(EventLoopOp::Minimized {
-                window: self.inner.clone(),
-                waker: tx,
-            })
-            .await;
+        let minimized = if self.reactor.is_main_thread() {
+            self.inner.is_minimized()
+        } else {
+            let (tx, rx) = oneoff();
+            self.reactor
+                .push_event_loop_op(EventLoopOp::Minimized {
+                    window: self.inner.clone(),
+                    waker: tx,
+                })
+                .await;
+
+            rx.recv().await
+        };
 
-        rx.recv().await
+        if let Some(minimized) = minimized {
+            self.registration.set_minimized(minimized);
+        }
+        minimized
     }
 
     /// Set the window's maximization.
     pub async fn set_maximized(&self, maximized: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_maximized(maximized);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetMaximized {
@@ -827,6 +1256,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the window's maximization.
     pub async fn is_maximized(&self) -> bool {
+        if self.reactor.is_main_thread() {
+            return self.inner.is_maximized();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Maximized {
@@ -840,6 +1273,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's fullscreen state.
     pub async fn set_fullscreen(&self, fullscreen: Option<Fullscreen>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_fullscreen(fullscreen);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetFullscreen {
@@ -854,6 +1291,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the fullscreen state of the window.
     pub async fn fullscreen(&self) -> Option<Fullscreen> {
+        if self.reactor.is_main_thread() {
+            return self.inner.fullscreen();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Fullscreen {
@@ -867,6 +1308,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's decorations.
     pub async fn set_decorations(&self, decorations: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_decorations(decorations);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetDecorated {
@@ -916,11 +1361,15 @@ impl<TS: ThreadSafety> Window<TS> {
     /// [japanese]: https://support.apple.com/guide/japanese-input-method/use-the-candidate-window-jpim10262/6.3/mac/12.0
     #[inline]
     pub fn set_ime_cursor_area<P: Into<Position>, S: Into<Size>>(&self, position: P, size: S) {
-        self.inner.set_ime_cursor_area(position, size)
+        self.inner.set_ime_cursor_area(position.into(), size.into())
     }
 
     /// Get the window's decorations.
     pub async fn is_decorated(&self) -> bool {
+        if self.reactor.is_main_thread() {
+            return self.inner.is_decorated();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Decorated {
@@ -934,6 +1383,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window level.
     pub async fn set_window_level(&self, level: WindowLevel) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_window_level(level);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetWindowLevel {
@@ -948,6 +1401,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window icon.
     pub async fn set_window_icon(&self, icon: Option<Icon>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_window_icon(icon);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetWindowIcon {
@@ -962,6 +1419,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the IME position.
     pub async fn set_ime_position(&self, posn: impl Into<Position>, size: impl Into<Size>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_ime_cursor_area(posn.into(), size.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetImeCursorArea {
@@ -977,6 +1438,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set whether IME is allowed.
     pub async fn set_ime_allowed(&self, allowed: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_ime_allowed(allowed);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetImeAllowed {
@@ -991,6 +1456,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the IME purpose.
     pub async fn set_ime_purpose(&self, purpose: ImePurpose) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_ime_purpose(purpose);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetImePurpose {
@@ -1005,6 +1474,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Focus the window.
     pub async fn focus_window(&self) {
+        if self.reactor.is_main_thread() {
+            return self.inner.focus_window();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::FocusWindow {
@@ -1018,6 +1491,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Tell whether the window is focused.
     pub async fn is_focused(&self) -> bool {
+        if self.reactor.is_main_thread() {
+            return self.inner.has_focus();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Focused {
@@ -1031,6 +1508,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Request the user's attention.
     pub async fn request_user_attention(&self, request_type: Option<UserAttentionType>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.request_user_attention(request_type);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::RequestUserAttention {
@@ -1045,6 +1526,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's theme.
     pub async fn set_theme(&self, theme: Option<Theme>) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_theme(theme);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetTheme {
@@ -1059,6 +1544,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the window's theme.
     pub async fn theme(&self) -> Option<Theme> {
+        if self.reactor.is_main_thread() {
+            return self.inner.theme();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Theme {
@@ -1072,6 +1561,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the window's protected content.
     pub async fn set_content_protected(&self, protected: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_content_protected(protected);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetProtectedContent {
@@ -1086,6 +1579,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the title of the window.
     pub async fn title(&self) -> String {
+        if self.reactor.is_main_thread() {
+            return self.inner.title();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::Title {
@@ -1099,6 +1596,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the cursor icon.
     pub async fn set_cursor_icon(&self, icon: CursorIcon) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_cursor_icon(icon);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetCursorIcon {
@@ -1116,6 +1617,10 @@ impl<TS: ThreadSafety> Window<TS> {
         &self,
         posn: impl Into<Position>,
     ) -> Result<(), ExternalError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_cursor_position(posn.into());
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetCursorPosition {
@@ -1130,6 +1635,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the cursor's grab mode.
     pub async fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_cursor_grab(mode);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetCursorGrab {
@@ -1144,6 +1653,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the cursor's visibility.
     pub async fn set_cursor_visible(&self, visible: bool) {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_cursor_visible(visible);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetCursorVisible {
@@ -1158,6 +1671,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Drag the window.
     pub async fn drag_window(&self) -> Result<(), ExternalError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.drag_window();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::DragWindow {
@@ -1174,6 +1691,10 @@ impl<TS: ThreadSafety> Window<TS> {
         &self,
         direction: ResizeDirection,
     ) -> Result<(), ExternalError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.drag_resize_window(direction);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::DragResizeWindow {
@@ -1188,6 +1709,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Set the cursor hit test.
     pub async fn set_cursor_hittest(&self, hit_test: bool) -> Result<(), ExternalError> {
+        if self.reactor.is_main_thread() {
+            return self.inner.set_cursor_hittest(hit_test);
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::SetCursorHitTest {
@@ -1202,6 +1727,10 @@ impl<TS: ThreadSafety> Window<TS> {
 
     /// Get the current monitor of this window.
     pub async fn current_monitor(&self) -> Option<MonitorHandle> {
+        if self.reactor.is_main_thread() {
+            return self.inner.current_monitor();
+        }
+
         let (tx, rx) = oneoff();
         self.reactor
             .push_event_loop_op(EventLoopOp::CurrentMonitor {
@@ -1212,127 +1741,174 @@ impl<TS: ThreadSafety> Window<TS> {
 
         rx.recv().await
     }
+
+    /// Apply several changes to the window at once.
+    ///
+    /// The changes made to the [`WindowUpdate`] in the closure are sent to the event loop as a
+    /// single operation and applied in one pass, in the order documented on [`WindowUpdate`].
+    ///
+    /// ```no_run
+    /// # async fn f(window: async_winit::window::Window<async_winit::DefaultThreadSafety>) {
+    /// window
+    ///     .update(|tx| {
+    ///         tx.set_title("Hello!")
+    ///             .set_min_inner_size(Some(async_winit::dpi::LogicalSize::new(200.0, 100.0).into()))
+    ///             .set_visible(true);
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn update(&self, f: impl FnOnce(&mut WindowUpdate)) {
+        let mut update = WindowUpdate::new();
+        f(&mut update);
+
+        if update.is_empty() {
+            return;
+        }
+
+        if self.reactor.is_main_thread() {
+            return update.apply(&self.inner);
+        }
+
+        let (tx, rx) = oneoff();
+        self.reactor
+            .push_event_loop_op(EventLoopOp::Update {
+                window: self.inner.clone(),
+                update: Box::new(update),
+                waker: tx,
+            })
+            .await;
+
+        rx.recv().await
+    }
 }
 
 /// Waiting for events.
 impl<TS: ThreadSafety> Window<TS> {
     /// Get the handler for the `RedrawRequested` event.
     pub fn redraw_requested(&self) -> &Handler<(), TS> {
-        &self.registration.redraw_requested
+        self.registration.handler(keys::REDRAW_REQUESTED)
     }
 
     /// Get the handler for the `CloseRequested` event.
     pub fn close_requested(&self) -> &Handler<(), TS> {
-        &self.registration.close_requested
+        self.registration.handler(keys::CLOSE_REQUESTED)
     }
 
     /// Get the handler for the `Resized` event.
     pub fn resized(&self) -> &Handler<PhysicalSize<u32>, TS> {
-        &self.registration.resized
+        self.registration.handler(keys::RESIZED)
     }
 
     /// Get the handler for the `Moved` event.
     pub fn moved(&self) -> &Handler<PhysicalPosition<i32>, TS> {
-        &self.registration.moved
+        self.registration.handler(keys::MOVED)
     }
 
     /// Get handler for the `Destroyed` event.
     pub fn destroyed(&self) -> &Handler<(), TS> {
-        &self.registration.destroyed
+        self.registration.handler(keys::DESTROYED)
     }
 
     /// Get the handler for the `Focused` event.
     pub fn focused(&self) -> &Handler<bool, TS> {
-        &self.registration.focused
+        self.registration.handler(keys::FOCUSED)
     }
 
     /// Get the handler for the `KeyboardInput` event.
     pub fn keyboard_input(&self) -> &Handler<crate::event::KeyboardInput, TS> {
-        &self.registration.keyboard_input
+        self.registration.handler(keys::KEYBOARD_INPUT)
     }
 
     /// Get the handler for the `ModifiersChanged` event.
     pub fn modifiers_changed(&self) -> &Handler<crate::keyboard::ModifiersState, TS> {
-        &self.registration.modifiers_changed
+        self.registration.handler(keys::MODIFIERS_CHANGED)
     }
 
     /// Get the handler for the `ReceivedCharacter` event.
     pub fn received_character(&self) -> &Handler<char, TS> {
-        &self.registration.received_character
+        self.registration.handler(keys::RECEIVED_CHARACTER)
     }
 
     /// Get the handler for the `Ime` event.
     pub fn ime(&self) -> &Handler<crate::event::Ime, TS> {
-        &self.registration.ime
+        self.registration.handler(keys::IME)
     }
 
     /// Get the handler for the `CursorMoved` event.
     pub fn cursor_moved(&self) -> &Handler<crate::event::CursorMoved, TS> {
-        &self.registration.cursor_moved
+        self.registration.handler(keys::CURSOR_MOVED)
     }
 
     /// Get the handler for the `CursorEntered` event.
     pub fn cursor_entered(&self) -> &Handler<DeviceId, TS> {
-        &self.registration.cursor_entered
+        self.registration.handler(keys::CURSOR_ENTERED)
     }
 
     /// Get the handler for the `CursorLeft` event.
     pub fn cursor_left(&self) -> &Handler<DeviceId, TS> {
-        &self.registration.cursor_left
+        self.registration.handler(keys::CURSOR_LEFT)
     }
 
     /// Get the handle for the `MouseWheel` event.
     pub fn mouse_wheel(&self) -> &Handler<crate::event::MouseWheel, TS> {
-        &self.registration.mouse_wheel
+        self.registration.handler(keys::MOUSE_WHEEL)
     }
 
     /// Get the handle for the `MouseInput` event.
     pub fn mouse_input(&self) -> &Handler<crate::event::MouseInput, TS> {
-        &self.registration.mouse_input
+        self.registration.handler(keys::MOUSE_INPUT)
     }
 
     /// Get the handle for the `TouchpadMagnify` event.
     pub fn touchpad_magnify(&self) -> &Handler<crate::event::TouchpadMagnify, TS> {
-        &self.registration.touchpad_magnify
+        self.registration.handler(keys::TOUCHPAD_MAGNIFY)
     }
 
     /// Get the handle for the `TouchpadPressure` event.
     pub fn touchpad_pressure(&self) -> &Handler<crate::event::TouchpadPressure, TS> {
-        &self.registration.touchpad_pressure
+        self.registration.handler(keys::TOUCHPAD_PRESSURE)
     }
 
     /// Get the handle for the `Touch` event.
     pub fn touch(&self) -> &Handler<crate::event::Touch, TS> {
-        &self.registration.touch
+        self.registration.handler(keys::TOUCH)
     }
 
     /// Get the handle for the `ScaleFactorChanged` event.
     pub fn scale_factor_changed(&self) -> &Handler<crate::event::ScaleFactor, TS> {
-        &self.registration.scale_factor_changed
+        self.registration.handler(keys::SCALE_FACTOR_CHANGED)
     }
 
     /// Get the handle for the `TouchpadRotate` event.
     pub fn touchpad_rotate(&self) -> &Handler<crate::event::TouchpadRotate, TS> {
-        &self.registration.touchpad_rotate
+        self.registration.handler(keys::TOUCHPAD_ROTATE)
     }
 
     /// Get the handle for the `SmartMagnify` event.
     pub fn smart_magnify(&self) -> &Handler<DeviceId, TS> {
-        &self.registration.smart_magnify
+        self.registration.handler(keys::SMART_MAGNIFY)
     }
 
     /// Get the handle for the `AxisMotion` event.
     pub fn axis_motion(&self) -> &Handler<crate::event::AxisMotion, TS> {
-        &self.registration.axis_motion
+        self.registration.handler(keys::AXIS_MOTION)
     }
 
     /// Get the handle for the `ThemeChanged` event.
     pub fn theme_changed(&self) -> &Handler<Theme, TS> {
-        &self.registration.theme_changed
+        self.registration.handler(keys::THEME_CHANGED)
     }
 
     /// Get the handle for the `Occulded` event.
     pub fn occluded(&self) -> &Handler<bool, TS> {
-        &self.registration.occluded
+        self.registration.handler(keys::OCCLUDED)
+    }
+
+    /// Get a stream of frames to draw, paced to the refresh rate of the window's monitor.
+    ///
+    /// See [`Frames`] for more information.
+    pub async fn frames(&self) -> Frames<'_, TS> {
+        Frames::new(self).await
     }
 }
@@ -0,0 +1,147 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Delivering input in batches, once per event loop iteration.
+
+use super::Window;
+use crate::sync::{ThreadSafety, __private::*};
+
+use std::collections::VecDeque;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use futures_lite::Stream;
+use winit::event::WindowEvent;
+
+/// The number of batches that can wait to be yielded before new events are merged into the newest
+/// one.
+const MAX_READY_BATCHES: usize = 16;
+
+/// A stream of batches of window events.
+///
+/// This is returned by [`Window::input_batches`]. Each batch contains every event that the window
+/// received during one iteration of the event loop, from [`NewEvents`] to [`AboutToWait`], in the
+/// order in which they were received. Iterations in which the window received no events don't
+/// produce a batch.
+///
+/// Events are still delivered to the window's event handlers as usual.
+///
+/// If the stream falls behind by more than 16 batches, the events of later iterations are added to
+/// the newest batch instead of starting a new one, so that no input is lost.
+///
+/// [`NewEvents`]: crate::event::Event::NewEvents
+/// [`AboutToWait`]: crate::event::Event::AboutToWait
+pub struct InputBatches<'a, TS: ThreadSafety> {
+    /// The window that we are receiving events for.
+    window: &'a Window<TS>,
+
+    /// Our queue of batches.
+    queue: TS::Rc<BatchQueue<TS>>,
+}
+
+/// The batches for one [`InputBatches`] stream.
+pub(crate) struct BatchQueue<TS: ThreadSafety> {
+    state: TS::Mutex<BatchState>,
+}
+
+struct BatchState {
+    /// The events received during the current iteration.
+    current: Vec<WindowEvent>,
+
+    /// Batches that are ready to be yielded.
+    ready: VecDeque<Vec<WindowEvent>>,
+
+    /// The task waiting for a batch.
+    waker: Option<Waker>,
+}
+
+impl<TS: ThreadSafety> BatchQueue<TS> {
+    /// Add an event to the current batch.
+    pub(crate) fn push(&self, event: WindowEvent) {
+        self.state.lock().unwrap().current.push(event);
+    }
+
+    /// Finish the current batch.
+    pub(crate) fn flush(&self) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            if state.current.is_empty() {
+                return;
+            }
+
+            let mut batch = mem::take(&mut state.current);
+            if state.ready.len() < MAX_READY_BATCHES {
+                state.ready.push_back(batch);
+            } else if let Some(newest) = state.ready.back_mut() {
+                newest.append(&mut batch);
+            }
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Window<TS> {
+    /// Get a stream of the events this window receives, batched per event loop iteration.
+    ///
+    /// This is useful for running a simulation step once per iteration, with all of the input for
+    /// that iteration at hand. See [`InputBatches`] for more information.
+    pub fn input_batches(&self) -> InputBatches<'_, TS> {
+        let queue = TS::Rc::new(BatchQueue {
+            state: TS::Mutex::new(BatchState {
+                current: Vec::new(),
+                ready: VecDeque::new(),
+                waker: None,
+            }),
+        });
+
+        self.registration.add_batch_queue(queue.clone());
+        InputBatches {
+            window: self,
+            queue,
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Unpin for InputBatches<'_, TS> {}
+
+impl<TS: ThreadSafety> Stream for InputBatches<'_, TS> {
+    type Item = Vec<WindowEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.queue.state.lock().unwrap();
+
+        match state.ready.pop_front() {
+            Some(batch) => Poll::Ready(Some(batch)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Drop for InputBatches<'_, TS> {
+    fn drop(&mut self) {
+        self.window.registration.remove_batch_queue(&self.queue);
+    }
+}
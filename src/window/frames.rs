@@ -0,0 +1,256 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Frame scheduling for windows.
+
+use super::{VisibilityWatch, Window};
+use crate::handler::Waiter;
+use crate::sync::ThreadSafety;
+use crate::Timer;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_lite::Stream;
+
+/// The refresh rate to assume when the monitor doesn't report one, in millihertz.
+const DEFAULT_REFRESH_RATE: u32 = 60_000;
+
+/// A stream of frames for a window.
+///
+/// This is returned by [`Window::frames`]. Each item is yielded in response to a
+/// [`RedrawRequested`] event, and should be used to draw the window's contents.
+///
+/// The stream requests redraws on its own, at most once per refresh interval of the window's
+/// monitor. This means that redraw requests are coalesced: a frame is never drawn twice in the same
+/// refresh interval because of a redraw requested by this stream. Redraws are not requested while
+/// the window can't be seen, as reported by [`Window::visibility_state`]. Redraws requested by the
+/// system are still yielded as frames.
+///
+/// [`RedrawRequested`]: crate::event::WindowEvent::RedrawRequested
+pub struct Frames<'a, TS: ThreadSafety> {
+    /// The window that we are drawing to.
+    window: &'a Window<TS>,
+
+    /// Waiter for `RedrawRequested` events.
+    redraw: Waiter<'a, (), TS>,
+
+    /// Watches whether the window is visible.
+    visibility: VisibilityWatch<'a, TS>,
+
+    /// The timer for the next frame.
+    timer: Timer<TS>,
+
+    /// The time between two refreshes of the monitor.
+    interval: Duration,
+
+    /// Whether the window is visible.
+    is_visible: bool,
+
+    /// Whether we requested a redraw that hasn't arrived yet.
+    requested: bool,
+
+    /// The time of the last frame.
+    last: Option<Instant>,
+
+    /// Frame time statistics.
+    stats: FrameStats,
+}
+
+/// A single frame yielded by [`Frames`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// The number of this frame, starting at zero.
+    pub number: u64,
+
+    /// The time at which this frame started.
+    pub timestamp: Instant,
+
+    /// The time since the previous frame.
+    ///
+    /// This is zero for the first frame, and for the first frame after the window becomes visible
+    /// again.
+    pub delta: Duration,
+}
+
+/// Frame time statistics for a [`Frames`] stream.
+///
+/// The time between two frames is only measured while the window is visible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FrameStats {
+    /// The number of frames that were yielded.
+    pub frames: u64,
+
+    /// The number of refresh intervals that passed without a frame.
+    pub missed: u64,
+
+    /// The average time between two frames.
+    pub average: Duration,
+
+    /// The shortest time between two frames.
+    pub min: Duration,
+
+    /// The longest time between two frames.
+    pub max: Duration,
+
+    /// The sum of the measured times between frames.
+    total: Duration,
+
+    /// The number of measured times between frames.
+    samples: u32,
+}
+
+impl<'a, TS: ThreadSafety> Frames<'a, TS> {
+    /// Create a new frame stream for a window.
+    pub(super) async fn new(window: &'a Window<TS>) -> Frames<'a, TS> {
+        let mut frames = Frames {
+            window,
+            redraw: window.redraw_requested().wait(),
+            visibility: window.watch_visibility(),
+            timer: Timer::never(),
+            interval: refresh_interval(DEFAULT_REFRESH_RATE),
+            is_visible: true,
+            requested: false,
+            last: None,
+            stats: FrameStats::default(),
+        };
+
+        frames.update_refresh_rate().await;
+        frames
+    }
+
+    /// Get the time between two refreshes of the window's monitor.
+    pub fn refresh_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Query the refresh rate of the window's current monitor again.
+    ///
+    /// The refresh rate is queried when the stream is created. This should be called when the
+    /// window may have moved to a different monitor.
+    pub async fn update_refresh_rate(&mut self) {
+        let rate = self
+            .window
+            .current_monitor()
+            .await
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .filter(|&rate| rate > 0)
+            .unwrap_or(DEFAULT_REFRESH_RATE);
+
+        self.interval = refresh_interval(rate);
+    }
+
+    /// Get the frame time statistics.
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Record a new frame.
+    fn record(&mut self, now: Instant) -> Frame {
+        let delta = match self.last.replace(now) {
+            Some(last) => now.saturating_duration_since(last),
+            None => Duration::ZERO,
+        };
+
+        let number = self.stats.frames;
+        self.stats.frames += 1;
+
+        if delta > Duration::ZERO {
+            let stats = &mut self.stats;
+
+            stats.min = if stats.samples == 0 {
+                delta
+            } else {
+                stats.min.min(delta)
+            };
+            stats.max = stats.max.max(delta);
+            stats.total = stats.total.saturating_add(delta);
+            stats.samples = stats.samples.saturating_add(1);
+            stats.average = stats.total / stats.samples;
+
+            // Count the refresh intervals that we skipped over, rounding to the nearest one.
+            let intervals = (delta + self.interval / 2).as_nanos() / self.interval.as_nanos();
+            stats.missed = stats
+                .missed
+                .saturating_add(intervals.saturating_sub(1) as u64);
+        }
+
+        Frame {
+            number,
+            timestamp: now,
+            delta,
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Unpin for Frames<'_, TS> {}
+
+impl<TS: ThreadSafety> Stream for Frames<'_, TS> {
+    type Item = Frame;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // Keep track of whether the window is visible.
+        while let Poll::Ready(Some(state)) = Pin::new(&mut this.visibility).poll_next(cx) {
+            if !this.is_visible && state.is_visible() {
+                // Draw as soon as the window is visible again.
+                this.timer.set_never();
+                this.requested = false;
+                this.last = None;
+            }
+
+            this.is_visible = state.is_visible();
+        }
+
+        // Request a redraw once the next refresh is due.
+        if !this.requested && this.is_visible {
+            let due = this.last.is_none() || Pin::new(&mut this.timer).poll(cx).is_ready();
+
+            if due {
+                this.window.request_redraw();
+                this.requested = true;
+            }
+        }
+
+        // Always take redraws, so that we never hold up the event loop.
+        match Pin::new(&mut this.redraw).poll_next(cx) {
+            Poll::Ready(Some(())) => {
+                let now = this.window.reactor.now();
+                let frame = this.record(now);
+
+                this.requested = false;
+                match now.checked_add(this.interval) {
+                    Some(next) => this.timer.set_at(next),
+                    None => this.timer.set_never(),
+                }
+
+                Poll::Ready(Some(frame))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Get the time between two refreshes at the given refresh rate, in millihertz.
+fn refresh_interval(rate: u32) -> Duration {
+    Duration::from_nanos(1_000_000_000_000 / u64::from(rate))
+}
@@ -0,0 +1,146 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! The window that backs a [`Window`](super::Window).
+
+use crate::dpi::{Position, Size};
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::error::{ExternalError, NotSupportedError};
+use winit::monitor::MonitorHandle;
+use winit::window::{
+    CursorGrabMode, CursorIcon, Fullscreen, Icon, ImePurpose, ResizeDirection, Theme,
+    UserAttentionType, WindowButtons, WindowId, WindowLevel,
+};
+
+/// The window that backs a [`Window`](super::Window).
+///
+/// This is either a real `winit` window or, for headless event loops, a window that only exists in
+/// memory.
+pub(crate) enum RawWindow {
+    /// A window created by `winit`.
+    Winit(winit::window::Window),
+
+    /// A window created by a headless event loop.
+    #[cfg(feature = "headless")]
+    Headless(crate::headless::HeadlessWindow),
+}
+
+/// Forward methods to whichever window backs the `RawWindow`.
+macro_rules! delegate {
+    ($(fn $name:ident(&self $(, $arg:ident: $ty:ty)*) $(-> $ret:ty)?;)*) => {
+        $(
+            pub(crate) fn $name(&self $(, $arg: $ty)*) $(-> $ret)? {
+                match self {
+                    Self::Winit(window) => window.$name($($arg),*),
+                    #[cfg(feature = "headless")]
+                    Self::Headless(window) => window.$name($($arg),*),
+                }
+            }
+        )*
+    };
+}
+
+impl RawWindow {
+    /// Get the `winit` window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a headless window.
+    pub(crate) fn winit(&self) -> &winit::window::Window {
+        match self {
+            Self::Winit(window) => window,
+            #[cfg(feature = "headless")]
+            Self::Headless(_) => panic!("headless windows are not backed by a `winit` window"),
+        }
+    }
+
+    /// Get the headless window, if this is one.
+    #[cfg(feature = "headless")]
+    pub(crate) fn headless(&self) -> Option<&crate::headless::HeadlessWindow> {
+        match self {
+            Self::Winit(_) => None,
+            Self::Headless(window) => Some(window),
+        }
+    }
+
+    delegate! {
+        fn id(&self) -> WindowId;
+        fn scale_factor(&self) -> f64;
+        fn request_redraw(&self);
+        fn inner_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError>;
+        fn outer_position(&self) -> Result<PhysicalPosition<i32>, NotSupportedError>;
+        fn set_outer_position(&self, position: Position);
+        fn inner_size(&self) -> PhysicalSize<u32>;
+        fn request_inner_size(&self, size: Size) -> Option<PhysicalSize<u32>>;
+        fn outer_size(&self) -> PhysicalSize<u32>;
+        fn set_min_inner_size(&self, min_size: Option<Size>);
+        fn set_max_inner_size(&self, max_size: Option<Size>);
+        fn resize_increments(&self) -> Option<PhysicalSize<u32>>;
+        fn set_resize_increments(&self, increments: Option<Size>);
+        fn title(&self) -> String;
+        fn set_title(&self, title: &str);
+        fn set_transparent(&self, transparent: bool);
+        fn is_visible(&self) -> Option<bool>;
+        fn set_visible(&self, visible: bool);
+        fn is_resizable(&self) -> bool;
+        fn set_resizable(&self, resizable: bool);
+        fn enabled_buttons(&self) -> WindowButtons;
+        fn set_enabled_buttons(&self, buttons: WindowButtons);
+        fn is_minimized(&self) -> Option<bool>;
+        fn set_minimized(&self, minimized: bool);
+        fn is_maximized(&self) -> bool;
+        fn set_maximized(&self, maximized: bool);
+        fn fullscreen(&self) -> Option<Fullscreen>;
+        fn set_fullscreen(&self, fullscreen: Option<Fullscreen>);
+        fn is_decorated(&self) -> bool;
+        fn set_decorations(&self, decorations: bool);
+        fn set_window_level(&self, level: WindowLevel);
+        fn set_window_icon(&self, window_icon: Option<Icon>);
+        fn theme(&self) -> Option<Theme>;
+        fn set_theme(&self, theme: Option<Theme>);
+        fn set_content_protected(&self, protected: bool);
+        fn has_focus(&self) -> bool;
+        fn focus_window(&self);
+        fn request_user_attention(&self, request_type: Option<UserAttentionType>);
+        fn set_ime_allowed(&self, allowed: bool);
+        fn set_ime_purpose(&self, purpose: ImePurpose);
+        fn set_ime_cursor_area(&self, position: Position, size: Size);
+        fn set_cursor_icon(&self, cursor: CursorIcon);
+        fn set_cursor_position(&self, position: Position) -> Result<(), ExternalError>;
+        fn set_cursor_grab(&self, mode: CursorGrabMode) -> Result<(), ExternalError>;
+        fn set_cursor_visible(&self, visible: bool);
+        fn set_cursor_hittest(&self, hittest: bool) -> Result<(), ExternalError>;
+        fn drag_window(&self) -> Result<(), ExternalError>;
+        fn drag_resize_window(&self, direction: ResizeDirection) -> Result<(), ExternalError>;
+        fn current_monitor(&self) -> Option<MonitorHandle>;
+    }
+}
+
+impl From<winit::window::Window> for RawWindow {
+    fn from(window: winit::window::Window) -> Self {
+        Self::Winit(window)
+    }
+}
+
+#[cfg(feature = "headless")]
+impl From<crate::headless::HeadlessWindow> for RawWindow {
+    fn from(window: crate::headless::HeadlessWindow) -> Self {
+        Self::Headless(window)
+    }
+}
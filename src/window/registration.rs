@@ -18,9 +18,14 @@ Public License along with `async-winit`. If not, see <https://www.gnu.org/licens
 
 //! Registration of the window into the reactor.
 
+use super::batches::BatchQueue;
+#[cfg(feature = "serde")]
+use super::surrogates;
+use super::RawWindow;
 use crate::dpi::PhysicalSize;
+use crate::error::ExternalError;
 use crate::handler::Handler;
-use crate::sync::ThreadSafety;
+use crate::sync::{ThreadSafety, __private::*};
 use crate::Event;
 
 use winit::dpi::PhysicalPosition;
@@ -28,59 +33,155 @@ use winit::event::{
     AxisId, DeviceId, ElementState, Ime, MouseButton, MouseScrollDelta, Touch, TouchPhase,
     WindowEvent,
 };
-use winit::keyboard::ModifiersState;
+use winit::keyboard::{Key, KeyLocation, ModifiersState, PhysicalKey, SmolStr};
 use winit::window::Theme;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct KeyboardInput {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
-    pub event: winit::event::KeyEvent,
+    pub event: KeyEvent,
     pub is_synthetic: bool,
 }
 
+/// A key press or release.
+///
+/// This has the same fields as `winit`'s [`KeyEvent`](winit::event::KeyEvent). Unlike it, it can be
+/// created, so that key events can be injected with [`Window::inject_key`].
+///
+/// [`Window::inject_key`]: crate::window::Window::inject_key
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyEvent {
+    pub physical_key: PhysicalKey,
+    pub logical_key: Key,
+    pub text: Option<SmolStr>,
+    pub location: KeyLocation,
+    pub state: ElementState,
+    pub repeat: bool,
+
+    /// The `winit` event that this was created from, if any.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    raw: Option<winit::event::KeyEvent>,
+}
+
+impl KeyEvent {
+    /// Create a key event that doesn't produce any text and isn't a repeat.
+    pub fn new(physical_key: PhysicalKey, logical_key: Key, state: ElementState) -> Self {
+        Self {
+            physical_key,
+            logical_key,
+            text: None,
+            location: KeyLocation::Standard,
+            state,
+            repeat: false,
+            raw: None,
+        }
+    }
+
+    /// Get the `winit` event that this was created from.
+    ///
+    /// This can be used with `winit`'s platform-specific extensions to key events. It is `None`
+    /// for key events that were created by the application.
+    pub fn winit_event(&self) -> Option<&winit::event::KeyEvent> {
+        self.raw.as_ref()
+    }
+}
+
+impl From<winit::event::KeyEvent> for KeyEvent {
+    fn from(event: winit::event::KeyEvent) -> Self {
+        Self {
+            physical_key: event.physical_key,
+            logical_key: event.logical_key.clone(),
+            text: event.text.clone(),
+            location: event.location,
+            state: event.state,
+            repeat: event.repeat,
+            raw: Some(event),
+        }
+    }
+}
+
+impl PartialEq for KeyEvent {
+    fn eq(&self, other: &Self) -> bool {
+        // Events from `winit` are equal to the ones created by the application.
+        self.physical_key == other.physical_key
+            && self.logical_key == other.logical_key
+            && self.text == other.text
+            && self.location == other.location
+            && self.state == other.state
+            && self.repeat == other.repeat
+    }
+}
+
+impl Eq for KeyEvent {}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CursorMoved {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub position: PhysicalPosition<f64>,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MouseWheel {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub delta: MouseScrollDelta,
     pub phase: TouchPhase,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MouseInput {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub state: ElementState,
     pub button: MouseButton,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TouchpadMagnify {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub delta: f64,
     pub phase: TouchPhase,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TouchpadRotate {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub delta: f32,
     pub phase: TouchPhase,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TouchpadPressure {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub pressure: f32,
     pub stage: i64,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AxisMotion {
+    #[cfg_attr(feature = "serde", serde(with = "surrogates::device_id"))]
     pub device_id: DeviceId,
     pub axis: AxisId,
     pub value: f64,
@@ -90,13 +191,53 @@ pub struct ScaleFactor;
 
 pub struct ScaleFactorChanging<'a> {
     pub scale_factor: f64,
-    pub inner_size_writer: &'a mut winit::event::InnerSizeWriter,
+    pub inner_size_writer: &'a mut InnerSizeWriter,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScaleFactorChanged {
     pub scale_factor: f64,
-    pub inner_size_writer: winit::event::InnerSizeWriter,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub inner_size_writer: InnerSizeWriter,
+}
+
+/// Sets the new inner size of a window after its scale factor changed.
+///
+/// This wraps `winit`'s [`InnerSizeWriter`](winit::event::InnerSizeWriter). Unlike it, it can be
+/// created, so that scale factor changes can be injected. A writer that was created by the
+/// application isn't connected to a window and ignores every request.
+#[derive(Debug, Clone, Default)]
+pub struct InnerSizeWriter {
+    /// The `winit` writer that this was created from, if any.
+    raw: Option<winit::event::InnerSizeWriter>,
+}
+
+impl InnerSizeWriter {
+    /// Create a writer that isn't connected to a window.
+    pub fn detached() -> Self {
+        Self { raw: None }
+    }
+
+    /// Request a new inner size for the window.
+    ///
+    /// Returns [`ExternalError::Ignored`] if the writer isn't connected to a window, or if the
+    /// window is no longer waiting for its new size.
+    pub fn request_inner_size(
+        &mut self,
+        new_inner_size: PhysicalSize<u32>,
+    ) -> Result<(), ExternalError> {
+        match &mut self.raw {
+            Some(raw) => raw.request_inner_size(new_inner_size),
+            None => Err(ExternalError::Ignored),
+        }
+    }
+}
+
+impl From<winit::event::InnerSizeWriter> for InnerSizeWriter {
+    fn from(raw: winit::event::InnerSizeWriter) -> Self {
+        Self { raw: Some(raw) }
+    }
 }
 
 impl Event for ScaleFactor {
@@ -112,166 +253,586 @@ impl Event for ScaleFactor {
 }
 
 pub(crate) struct Registration<TS: ThreadSafety> {
+    /// The handlers for the window's events.
+    ///
+    /// Many windows (tooltips, popups) never listen to most events. Each handler only allocates
+    /// its state once it is first used, so the unused ones take up little space.
+    handlers: Handlers<TS>,
+
+    /// The visibility of the window, as far as events tell us.
+    visibility: TS::Mutex<Visibility>,
+
+    /// The queues of the window's `InputBatches` streams.
+    batches: TS::Mutex<Vec<TS::Rc<BatchQueue<TS>>>>,
+
+    /// A weak reference to the window, so it can be looked up from the reactor.
+    window: TS::OnceLock<WeakWindow<TS>>,
+}
+
+/// A weak reference to a window.
+type WeakWindow<TS> = <<TS as __ThreadSafety>::Rc<RawWindow> as Rc<RawWindow>>::Weak;
+
+/// Tracks events that may change the visibility of a window.
+struct Visibility {
+    /// Whether the window is occluded.
+    occluded: bool,
+
+    /// Whether the window was minimized the last time we checked on the event loop thread.
+    minimized: bool,
+
+    /// Incremented every time the visibility may have changed.
+    version: u64,
+
+    /// Tasks waiting for the visibility to change.
+    wakers: Vec<Waker>,
+}
+
+/// The handlers for a window's events.
+///
+/// Most windows only listen to a few of their events, so instead of holding every handler, this
+/// is a list of the handlers that have been requested. A handler is added to the list the first
+/// time it is requested, and only allocates its state once something subscribes to it.
+pub(crate) struct Handlers<TS: ThreadSafety> {
+    /// The first handler in the list.
+    head: TS::OnceLock<Box<HandlerNode<TS>>>,
+}
+
+/// A handler in a window's list of handlers.
+struct HandlerNode<TS: ThreadSafety, H: ?Sized = dyn Any> {
+    /// The kind of event that the handler is for.
+    kind: HandlerKind,
+
+    /// The next handler in the list.
+    next: TS::OnceLock<Box<HandlerNode<TS>>>,
+
+    /// The `Handler<T, TS>` for `kind`, where `T` is the event type of its key.
+    handler: H,
+}
+
+// SAFETY: Only `Handler<T, ThreadSafe>` for the event types in `keys` are stored in nodes, which are
+// all `Send + Sync`.
+#[cfg(feature = "thread_safe")]
+unsafe impl Send for HandlerNode<crate::sync::ThreadSafe> {}
+#[cfg(feature = "thread_safe")]
+unsafe impl Sync for HandlerNode<crate::sync::ThreadSafe> {}
+
+#[cfg(feature = "thread_safe")]
+#[allow(dead_code)]
+fn _assert_handlers_are_thread_safe() {
+    fn assert_send_sync<T: Event>(_: HandlerKey<T>)
+    where
+        Handler<T, crate::sync::ThreadSafe>: Send + Sync,
+    {
+    }
+
+    assert_send_sync(keys::REDRAW_REQUESTED);
+    assert_send_sync(keys::CLOSE_REQUESTED);
+    assert_send_sync(keys::RESIZED);
+    assert_send_sync(keys::MOVED);
+    assert_send_sync(keys::DESTROYED);
+    assert_send_sync(keys::FOCUSED);
+    assert_send_sync(keys::RECEIVED_CHARACTER);
+    assert_send_sync(keys::KEYBOARD_INPUT);
+    assert_send_sync(keys::MODIFIERS_CHANGED);
+    assert_send_sync(keys::IME);
+    assert_send_sync(keys::CURSOR_MOVED);
+    assert_send_sync(keys::CURSOR_ENTERED);
+    assert_send_sync(keys::CURSOR_LEFT);
+    assert_send_sync(keys::MOUSE_WHEEL);
+    assert_send_sync(keys::MOUSE_INPUT);
+    assert_send_sync(keys::TOUCHPAD_MAGNIFY);
+    assert_send_sync(keys::SMART_MAGNIFY);
+    assert_send_sync(keys::TOUCHPAD_ROTATE);
+    assert_send_sync(keys::TOUCHPAD_PRESSURE);
+    assert_send_sync(keys::AXIS_MOTION);
+    assert_send_sync(keys::TOUCH);
+    assert_send_sync(keys::SCALE_FACTOR_CHANGED);
+    assert_send_sync(keys::THEME_CHANGED);
+    assert_send_sync(keys::OCCLUDED);
+}
+
+/// The kinds of events that a window has handlers for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandlerKind {
+    RedrawRequested,
+    CloseRequested,
+    Resized,
+    Moved,
+    Destroyed,
+    Focused,
+    ReceivedCharacter,
+    KeyboardInput,
+    ModifiersChanged,
+    Ime,
+    CursorMoved,
+    CursorEntered,
+    CursorLeft,
+    MouseWheel,
+    MouseInput,
+    TouchpadMagnify,
+    SmartMagnify,
+    TouchpadRotate,
+    TouchpadPressure,
+    AxisMotion,
+    Touch,
+    ScaleFactorChanged,
+    ThemeChanged,
+    Occluded,
+}
+
+/// Identifies one of a window's handlers, along with the type of event that it handles.
+pub(crate) struct HandlerKey<T> {
+    kind: HandlerKind,
+    event: PhantomData<fn() -> T>,
+}
+
+impl<T> HandlerKey<T> {
+    const fn new(kind: HandlerKind) -> Self {
+        Self {
+            kind,
+            event: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for HandlerKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for HandlerKey<T> {}
+
+/// The keys of a window's handlers.
+pub(crate) mod keys {
+    use super::*;
+
     /// `RedrawRequested`
-    pub(crate) redraw_requested: Handler<(), TS>,
+    pub(crate) const REDRAW_REQUESTED: HandlerKey<()> =
+        HandlerKey::new(HandlerKind::RedrawRequested);
 
     /// `Event::CloseRequested`.
-    pub(crate) close_requested: Handler<(), TS>,
+    pub(crate) const CLOSE_REQUESTED: HandlerKey<()> = HandlerKey::new(HandlerKind::CloseRequested);
 
     /// `Event::Resized`.
-    pub(crate) resized: Handler<PhysicalSize<u32>, TS>,
+    pub(crate) const RESIZED: HandlerKey<PhysicalSize<u32>> = HandlerKey::new(HandlerKind::Resized);
 
     /// `Event::Moved`.
-    pub(crate) moved: Handler<PhysicalPosition<i32>, TS>,
+    pub(crate) const MOVED: HandlerKey<PhysicalPosition<i32>> = HandlerKey::new(HandlerKind::Moved);
 
     /// `Event::Destroyed`.
-    pub(crate) destroyed: Handler<(), TS>,
+    pub(crate) const DESTROYED: HandlerKey<()> = HandlerKey::new(HandlerKind::Destroyed);
 
     /// `Event::Focused`.
-    pub(crate) focused: Handler<bool, TS>,
+    pub(crate) const FOCUSED: HandlerKey<bool> = HandlerKey::new(HandlerKind::Focused);
 
     /// `Event::ReceivedCharacter`.
-    pub(crate) received_character: Handler<char, TS>,
+    pub(crate) const RECEIVED_CHARACTER: HandlerKey<char> =
+        HandlerKey::new(HandlerKind::ReceivedCharacter);
 
     /// `Event::KeyboardInput`.
-    pub(crate) keyboard_input: Handler<KeyboardInput, TS>,
+    pub(crate) const KEYBOARD_INPUT: HandlerKey<KeyboardInput> =
+        HandlerKey::new(HandlerKind::KeyboardInput);
 
     /// `Event::ModifiersState`
-    pub(crate) modifiers_changed: Handler<ModifiersState, TS>,
+    pub(crate) const MODIFIERS_CHANGED: HandlerKey<ModifiersState> =
+        HandlerKey::new(HandlerKind::ModifiersChanged);
 
     /// `Event::Ime`
-    pub(crate) ime: Handler<Ime, TS>,
+    pub(crate) const IME: HandlerKey<Ime> = HandlerKey::new(HandlerKind::Ime);
 
     /// `Event::CursorMoved`
-    pub(crate) cursor_moved: Handler<CursorMoved, TS>,
+    pub(crate) const CURSOR_MOVED: HandlerKey<CursorMoved> =
+        HandlerKey::new(HandlerKind::CursorMoved);
 
     /// `Event::CursorEntered`
-    pub(crate) cursor_entered: Handler<DeviceId, TS>,
+    pub(crate) const CURSOR_ENTERED: HandlerKey<DeviceId> =
+        HandlerKey::new(HandlerKind::CursorEntered);
 
     /// `Event::CursorLeft`
-    pub(crate) cursor_left: Handler<DeviceId, TS>,
+    pub(crate) const CURSOR_LEFT: HandlerKey<DeviceId> = HandlerKey::new(HandlerKind::CursorLeft);
 
     /// `Event::MouseWheel`
-    pub(crate) mouse_wheel: Handler<MouseWheel, TS>,
+    pub(crate) const MOUSE_WHEEL: HandlerKey<MouseWheel> = HandlerKey::new(HandlerKind::MouseWheel);
 
     /// `Event::MouseInput`
-    pub(crate) mouse_input: Handler<MouseInput, TS>,
+    pub(crate) const MOUSE_INPUT: HandlerKey<MouseInput> = HandlerKey::new(HandlerKind::MouseInput);
 
     /// `Event::TouchpadMagnify`
-    pub(crate) touchpad_magnify: Handler<TouchpadMagnify, TS>,
+    pub(crate) const TOUCHPAD_MAGNIFY: HandlerKey<TouchpadMagnify> =
+        HandlerKey::new(HandlerKind::TouchpadMagnify);
 
     /// `Event::SmartMagnify`.
-    pub(crate) smart_magnify: Handler<DeviceId, TS>,
+    pub(crate) const SMART_MAGNIFY: HandlerKey<DeviceId> =
+        HandlerKey::new(HandlerKind::SmartMagnify);
 
     /// `Event::TouchpadRotate`
-    pub(crate) touchpad_rotate: Handler<TouchpadRotate, TS>,
+    pub(crate) const TOUCHPAD_ROTATE: HandlerKey<TouchpadRotate> =
+        HandlerKey::new(HandlerKind::TouchpadRotate);
 
     /// `Event::TouchpadPressure`
-    pub(crate) touchpad_pressure: Handler<TouchpadPressure, TS>,
+    pub(crate) const TOUCHPAD_PRESSURE: HandlerKey<TouchpadPressure> =
+        HandlerKey::new(HandlerKind::TouchpadPressure);
 
     /// `Event::AxisMotion`
-    pub(crate) axis_motion: Handler<AxisMotion, TS>,
+    pub(crate) const AXIS_MOTION: HandlerKey<AxisMotion> = HandlerKey::new(HandlerKind::AxisMotion);
 
     /// `Event::Touch`
-    pub(crate) touch: Handler<Touch, TS>,
+    pub(crate) const TOUCH: HandlerKey<Touch> = HandlerKey::new(HandlerKind::Touch);
 
     /// `Event::ScaleFactorChanged`
-    pub(crate) scale_factor_changed: Handler<ScaleFactor, TS>,
+    pub(crate) const SCALE_FACTOR_CHANGED: HandlerKey<ScaleFactor> =
+        HandlerKey::new(HandlerKind::ScaleFactorChanged);
 
     /// `Event::ThemeChanged`
-    pub(crate) theme_changed: Handler<Theme, TS>,
+    pub(crate) const THEME_CHANGED: HandlerKey<Theme> = HandlerKey::new(HandlerKind::ThemeChanged);
 
     /// `Event::Occluded`
-    pub(crate) occluded: Handler<bool, TS>,
+    pub(crate) const OCCLUDED: HandlerKey<bool> = HandlerKey::new(HandlerKind::Occluded);
 }
 
 impl<TS: ThreadSafety> Registration<TS> {
     pub(crate) fn new() -> Self {
         Self {
-            close_requested: Handler::new(),
-            resized: Handler::new(),
-            redraw_requested: Handler::new(),
-            moved: Handler::new(),
-            destroyed: Handler::new(),
-            focused: Handler::new(),
-            keyboard_input: Handler::new(),
-            received_character: Handler::new(),
-            modifiers_changed: Handler::new(),
-            ime: Handler::new(),
-            cursor_entered: Handler::new(),
-            cursor_left: Handler::new(),
-            cursor_moved: Handler::new(),
-            axis_motion: Handler::new(),
-            scale_factor_changed: Handler::new(),
-            smart_magnify: Handler::new(),
-            theme_changed: Handler::new(),
-            touch: Handler::new(),
-            touchpad_magnify: Handler::new(),
-            touchpad_pressure: Handler::new(),
-            touchpad_rotate: Handler::new(),
-            mouse_input: Handler::new(),
-            mouse_wheel: Handler::new(),
-            occluded: Handler::new(),
+            handlers: Handlers::new(),
+            visibility: TS::Mutex::new(Visibility {
+                occluded: false,
+                minimized: false,
+                version: 0,
+                wakers: Vec::new(),
+            }),
+            batches: TS::Mutex::new(Vec::new()),
+            window: TS::OnceLock::new(),
+        }
+    }
+
+    /// Set the window that this registration belongs to.
+    pub(crate) fn set_window(&self, window: &TS::Rc<RawWindow>) {
+        self.window.set(Rc::downgrade(window)).ok();
+    }
+
+    /// Get the window that this registration belongs to, if it still exists.
+    pub(crate) fn window(&self) -> Option<TS::Rc<RawWindow>> {
+        TS::Rc::upgrade(self.window.get()?)
+    }
+
+    /// Start collecting batches of events into a queue.
+    pub(crate) fn add_batch_queue(&self, queue: TS::Rc<BatchQueue<TS>>) {
+        self.batches.lock().unwrap().push(queue);
+    }
+
+    /// Stop collecting batches of events into a queue.
+    pub(crate) fn remove_batch_queue(&self, queue: &TS::Rc<BatchQueue<TS>>) {
+        self.batches
+            .lock()
+            .unwrap()
+            .retain(|other| !TS::Rc::ptr_eq(other, queue));
+    }
+
+    /// Add an event to the current batch of every queue.
+    pub(crate) fn push_batched(&self, event: &WindowEvent) {
+        for queue in self.batches.lock().unwrap().iter() {
+            queue.push(event.clone());
         }
     }
 
-    pub(crate) async fn signal(&self, event: WindowEvent) {
+    /// Finish the current batch of every queue.
+    pub(crate) fn flush_batches(&self) {
+        for queue in self.batches.lock().unwrap().iter() {
+            queue.flush();
+        }
+    }
+
+    /// Tell whether the last `Occluded` event marked the window as occluded.
+    pub(crate) fn is_occluded(&self) -> bool {
+        self.visibility.lock().unwrap().occluded
+    }
+
+    /// Tell whether the window was minimized when we last checked.
+    pub(crate) fn is_minimized(&self) -> bool {
+        self.visibility.lock().unwrap().minimized
+    }
+
+    /// Record whether the window is minimized, after querying or changing it.
+    pub(crate) fn set_minimized(&self, minimized: bool) {
+        let changed = {
+            let mut visibility = self.visibility.lock().unwrap();
+            std::mem::replace(&mut visibility.minimized, minimized) != minimized
+        };
+
+        if changed {
+            self.visibility_changed();
+        }
+    }
+
+    /// Update the window's visibility from an event.
+    ///
+    /// This runs on the event loop thread for every event, whether or not anything is listening
+    /// for it.
+    pub(crate) fn update_visibility(&self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Occluded(occluded) => {
+                self.visibility.lock().unwrap().occluded = *occluded;
+                self.refresh_minimized();
+                self.visibility_changed();
+            }
+
+            // Minimizing or restoring a window usually resizes or (un)focuses it.
+            WindowEvent::Resized(_) | WindowEvent::Focused(_) => {
+                self.refresh_minimized();
+                self.visibility_changed();
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Query whether the window is minimized.
+    ///
+    /// This must only be called on the event loop thread.
+    fn refresh_minimized(&self) {
+        if let Some(window) = self.window() {
+            let minimized = window.is_minimized() == Some(true);
+            self.visibility.lock().unwrap().minimized = minimized;
+        }
+    }
+
+    /// Wake up the tasks watching the window's visibility.
+    pub(crate) fn visibility_changed(&self) {
+        let wakers = {
+            let mut visibility = self.visibility.lock().unwrap();
+            visibility.version = visibility.version.wrapping_add(1);
+            std::mem::take(&mut visibility.wakers)
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Wait until the visibility may have changed since the version in `seen`.
+    pub(crate) fn poll_visibility(&self, seen: &mut Option<u64>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut visibility = self.visibility.lock().unwrap();
+
+        if *seen != Some(visibility.version) {
+            *seen = Some(visibility.version);
+            return Poll::Ready(());
+        }
+
+        if !visibility.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            visibility.wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    /// Get one of the window's handlers, adding it if needed.
+    pub(crate) fn handler<T: Event + 'static>(&self, key: HandlerKey<T>) -> &Handler<T, TS> {
+        self.handlers.get_or_insert(key)
+    }
+
+    /// Tell whether anything is listening for this event.
+    pub(crate) fn is_listening(&self, event: &WindowEvent) -> bool {
+        use keys::*;
+
+        let handlers = &self.handlers;
+
+        match event {
+            WindowEvent::RedrawRequested => handlers.has_listeners(REDRAW_REQUESTED),
+            WindowEvent::CloseRequested => handlers.has_listeners(CLOSE_REQUESTED),
+            WindowEvent::Resized(_) => handlers.has_listeners(RESIZED),
+            WindowEvent::Moved(_) => handlers.has_listeners(MOVED),
+            WindowEvent::AxisMotion { .. } => handlers.has_listeners(AXIS_MOTION),
+            WindowEvent::CursorEntered { .. } => handlers.has_listeners(CURSOR_ENTERED),
+            WindowEvent::CursorLeft { .. } => handlers.has_listeners(CURSOR_LEFT),
+            WindowEvent::CursorMoved { .. } => handlers.has_listeners(CURSOR_MOVED),
+            WindowEvent::Destroyed => handlers.has_listeners(DESTROYED),
+            WindowEvent::Focused(_) => handlers.has_listeners(FOCUSED),
+            WindowEvent::Ime(_) => handlers.has_listeners(IME),
+            WindowEvent::KeyboardInput { .. } => handlers.has_listeners(KEYBOARD_INPUT),
+            WindowEvent::ModifiersChanged(_) => handlers.has_listeners(MODIFIERS_CHANGED),
+            WindowEvent::MouseInput { .. } => handlers.has_listeners(MOUSE_INPUT),
+            WindowEvent::MouseWheel { .. } => handlers.has_listeners(MOUSE_WHEEL),
+            WindowEvent::Occluded(_) => handlers.has_listeners(OCCLUDED),
+            WindowEvent::ScaleFactorChanged { .. } => handlers.has_listeners(SCALE_FACTOR_CHANGED),
+            WindowEvent::SmartMagnify { .. } => handlers.has_listeners(SMART_MAGNIFY),
+            WindowEvent::ThemeChanged(_) => handlers.has_listeners(THEME_CHANGED),
+            WindowEvent::Touch(_) => handlers.has_listeners(TOUCH),
+            WindowEvent::TouchpadMagnify { .. } => handlers.has_listeners(TOUCHPAD_MAGNIFY),
+            WindowEvent::TouchpadPressure { .. } => handlers.has_listeners(TOUCHPAD_PRESSURE),
+            WindowEvent::TouchpadRotate { .. } => handlers.has_listeners(TOUCHPAD_ROTATE),
+            _ => false,
+        }
+    }
+
+    pub(crate) async fn signal(&self, event: WindowEvent, timestamp: Instant) {
+        self.handlers.signal(event, timestamp).await;
+    }
+
+    /// Dispatch keyboard input that didn't come from `winit`.
+    pub(crate) async fn signal_keyboard_input(&self, mut input: KeyboardInput, timestamp: Instant) {
+        self.handlers
+            .run(keys::KEYBOARD_INPUT, &mut input, timestamp)
+            .await;
+    }
+
+    /// Dispatch a scale factor change that didn't come from `winit`.
+    #[cfg(feature = "record")]
+    pub(crate) async fn signal_scale_factor_changed(
+        &self,
+        scale_factor: f64,
+        inner_size_writer: InnerSizeWriter,
+        timestamp: Instant,
+    ) {
+        self.handlers
+            .signal_scale_factor_changed(scale_factor, inner_size_writer, timestamp)
+            .await;
+    }
+}
+
+impl<TS: ThreadSafety> HandlerNode<TS> {
+    /// Get the handler in this node.
+    fn downcast<T: Event + 'static>(&self) -> &Handler<T, TS> {
+        self.handler
+            .downcast_ref()
+            .expect("window handler has the wrong type")
+    }
+}
+
+impl<TS: ThreadSafety> Handlers<TS> {
+    fn new() -> Self {
+        Self {
+            head: TS::OnceLock::new(),
+        }
+    }
+
+    /// Get a handler, if it has been added.
+    fn get<T: Event + 'static>(&self, key: HandlerKey<T>) -> Option<&Handler<T, TS>> {
+        let mut next = self.head.get();
+        while let Some(node) = next {
+            if node.kind == key.kind {
+                return Some(node.downcast());
+            }
+            next = node.next.get();
+        }
+
+        None
+    }
+
+    /// Get a handler, adding it to the end of the list if it hasn't been added yet.
+    fn get_or_insert<T: Event + 'static>(&self, key: HandlerKey<T>) -> &Handler<T, TS> {
+        let mut new: Option<Box<HandlerNode<TS>>> = None;
+        let mut link = &self.head;
+
+        loop {
+            match link.get() {
+                Some(node) if node.kind == key.kind => return node.downcast(),
+                Some(node) => link = &node.next,
+                None => {
+                    let node = new.take().unwrap_or_else(|| {
+                        Box::new(HandlerNode {
+                            kind: key.kind,
+                            next: TS::OnceLock::new(),
+                            handler: Handler::<T, TS>::new(),
+                        })
+                    });
+
+                    // If another thread added a handler first, look at it and try again.
+                    if let Err(node) = link.set(node) {
+                        new = Some(node);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tell whether anything is listening to a handler.
+    fn has_listeners<T: Event + 'static>(&self, key: HandlerKey<T>) -> bool {
+        self.get(key).map_or(false, Handler::has_listeners)
+    }
+
+    /// Dispatch an event to a handler, if it has been added.
+    async fn run<T: Event + 'static>(
+        &self,
+        key: HandlerKey<T>,
+        event: &mut T::Unique<'_>,
+        timestamp: Instant,
+    ) {
+        if let Some(handler) = self.get(key) {
+            handler.run_with(event, timestamp).await;
+        }
+    }
+
+    async fn signal(&self, event: WindowEvent, timestamp: Instant) {
         match event {
             WindowEvent::RedrawRequested => {
-                self.redraw_requested.run_with(&mut ()).await;
+                self.run(keys::REDRAW_REQUESTED, &mut (), timestamp).await;
+            }
+            WindowEvent::CloseRequested => {
+                self.run(keys::CLOSE_REQUESTED, &mut (), timestamp).await
             }
-            WindowEvent::CloseRequested => self.close_requested.run_with(&mut ()).await,
-            WindowEvent::Resized(mut size) => self.resized.run_with(&mut size).await,
-            WindowEvent::Moved(mut posn) => self.moved.run_with(&mut posn).await,
+            WindowEvent::Resized(mut size) => self.run(keys::RESIZED, &mut size, timestamp).await,
+            WindowEvent::Moved(mut posn) => self.run(keys::MOVED, &mut posn, timestamp).await,
             WindowEvent::AxisMotion {
                 device_id,
                 axis,
                 value,
             } => {
-                self.axis_motion
-                    .run_with(&mut AxisMotion {
+                self.run(
+                    keys::AXIS_MOTION,
+                    &mut AxisMotion {
                         device_id,
                         axis,
                         value,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             WindowEvent::CursorEntered { mut device_id } => {
-                self.cursor_entered.run_with(&mut device_id).await
+                self.run(keys::CURSOR_ENTERED, &mut device_id, timestamp)
+                    .await
             }
             WindowEvent::CursorLeft { mut device_id } => {
-                self.cursor_left.run_with(&mut device_id).await
+                self.run(keys::CURSOR_LEFT, &mut device_id, timestamp).await
             }
             WindowEvent::CursorMoved {
                 device_id,
                 position,
                 ..
             } => {
-                self.cursor_moved
-                    .run_with(&mut CursorMoved {
+                self.run(
+                    keys::CURSOR_MOVED,
+                    &mut CursorMoved {
                         device_id,
                         position,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
-            WindowEvent::Destroyed => self.destroyed.run_with(&mut ()).await,
-            WindowEvent::Focused(mut foc) => self.focused.run_with(&mut foc).await,
-            WindowEvent::Ime(mut ime) => self.ime.run_with(&mut ime).await,
+            WindowEvent::Destroyed => self.run(keys::DESTROYED, &mut (), timestamp).await,
+            WindowEvent::Focused(mut foc) => self.run(keys::FOCUSED, &mut foc, timestamp).await,
+            WindowEvent::Ime(mut ime) => self.run(keys::IME, &mut ime, timestamp).await,
             WindowEvent::KeyboardInput {
                 device_id,
                 event,
                 is_synthetic,
             } => {
-                self.keyboard_input
-                    .run_with(&mut KeyboardInput {
+                self.run(
+                    keys::KEYBOARD_INPUT,
+                    &mut KeyboardInput {
                         device_id,
-                        event,
+                        event: event.into(),
                         is_synthetic,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             WindowEvent::ModifiersChanged(mods) => {
-                self.modifiers_changed.run_with(&mut mods.state()).await
+                self.run(keys::MODIFIERS_CHANGED, &mut mods.state(), timestamp)
+                    .await
             }
             WindowEvent::MouseInput {
                 device_id,
@@ -279,13 +840,16 @@ impl<TS: ThreadSafety> Registration<TS> {
                 button,
                 ..
             } => {
-                self.mouse_input
-                    .run_with(&mut MouseInput {
+                self.run(
+                    keys::MOUSE_INPUT,
+                    &mut MouseInput {
                         device_id,
                         state,
                         button,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             WindowEvent::MouseWheel {
                 device_id,
@@ -293,71 +857,99 @@ impl<TS: ThreadSafety> Registration<TS> {
                 phase,
                 ..
             } => {
-                self.mouse_wheel
-                    .run_with(&mut MouseWheel {
+                self.run(
+                    keys::MOUSE_WHEEL,
+                    &mut MouseWheel {
                         device_id,
                         delta,
                         phase,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
-            WindowEvent::Occluded(mut occ) => self.occluded.run_with(&mut occ).await,
+            WindowEvent::Occluded(mut occ) => self.run(keys::OCCLUDED, &mut occ, timestamp).await,
             WindowEvent::ScaleFactorChanged {
                 scale_factor,
-                mut inner_size_writer,
+                inner_size_writer,
             } => {
-                self.scale_factor_changed
-                    .run_with(&mut ScaleFactorChanging {
-                        scale_factor,
-                        inner_size_writer: &mut inner_size_writer,
-                    })
+                self.signal_scale_factor_changed(scale_factor, inner_size_writer.into(), timestamp)
                     .await
             }
             WindowEvent::SmartMagnify { mut device_id } => {
-                self.smart_magnify.run_with(&mut device_id).await
+                self.run(keys::SMART_MAGNIFY, &mut device_id, timestamp)
+                    .await
             }
-            WindowEvent::ThemeChanged(mut theme) => self.theme_changed.run_with(&mut theme).await,
-            WindowEvent::Touch(mut touch) => self.touch.run_with(&mut touch).await,
+            WindowEvent::ThemeChanged(mut theme) => {
+                self.run(keys::THEME_CHANGED, &mut theme, timestamp).await
+            }
+            WindowEvent::Touch(mut touch) => self.run(keys::TOUCH, &mut touch, timestamp).await,
             WindowEvent::TouchpadMagnify {
                 device_id,
                 delta,
                 phase,
             } => {
-                self.touchpad_magnify
-                    .run_with(&mut TouchpadMagnify {
+                self.run(
+                    keys::TOUCHPAD_MAGNIFY,
+                    &mut TouchpadMagnify {
                         device_id,
                         delta,
                         phase,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             WindowEvent::TouchpadPressure {
                 device_id,
                 pressure,
                 stage,
             } => {
-                self.touchpad_pressure
-                    .run_with(&mut TouchpadPressure {
+                self.run(
+                    keys::TOUCHPAD_PRESSURE,
+                    &mut TouchpadPressure {
                         device_id,
                         pressure,
                         stage,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             WindowEvent::TouchpadRotate {
                 device_id,
                 delta,
                 phase,
             } => {
-                self.touchpad_rotate
-                    .run_with(&mut TouchpadRotate {
+                self.run(
+                    keys::TOUCHPAD_ROTATE,
+                    &mut TouchpadRotate {
                         device_id,
                         delta,
                         phase,
-                    })
-                    .await
+                    },
+                    timestamp,
+                )
+                .await
             }
             _ => {}
         }
     }
+
+    async fn signal_scale_factor_changed(
+        &self,
+        scale_factor: f64,
+        mut inner_size_writer: InnerSizeWriter,
+        timestamp: Instant,
+    ) {
+        self.run(
+            keys::SCALE_FACTOR_CHANGED,
+            &mut ScaleFactorChanging {
+                scale_factor,
+                inner_size_writer: &mut inner_size_writer,
+            },
+            timestamp,
+        )
+        .await
+    }
 }
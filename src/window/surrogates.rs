@@ -0,0 +1,210 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Serialized forms of `winit` types that don't implement `Serialize` and `Deserialize`.
+
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, PoisonError};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use winit::event::DeviceId;
+
+/// A [`DeviceId`], in the form that it is serialized in.
+///
+/// `winit` doesn't allow device IDs to be serialized or created, so they are serialized as a
+/// number computed by hashing the device ID with 64-bit [FNV-1a]. This hash doesn't depend on the
+/// process or on the version of Rust, so the same device ID is always serialized as the same
+/// number on a given platform.
+///
+/// Deserializing a device ID gives back the original device ID if it was serialized or converted
+/// into a `SerializedDeviceId` by this process, and a placeholder ID otherwise.
+///
+/// [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SerializedDeviceId(pub u64);
+
+/// Device IDs that were serialized by this process, so that they can be deserialized.
+static DEVICE_IDS: Mutex<Vec<(SerializedDeviceId, DeviceId)>> = Mutex::new(Vec::new());
+
+impl SerializedDeviceId {
+    /// Get the serialized form of a device ID.
+    pub fn new(device_id: &DeviceId) -> Self {
+        let mut hasher = Fnv1a(Fnv1a::OFFSET_BASIS);
+        device_id.hash(&mut hasher);
+        let id = Self(hasher.finish());
+
+        // Remember the device ID, so that it can be deserialized.
+        let mut device_ids = DEVICE_IDS.lock().unwrap_or_else(PoisonError::into_inner);
+        if !device_ids.iter().any(|&(known, _)| known == id) {
+            device_ids.push((id, *device_id));
+        }
+
+        id
+    }
+
+    /// Get the device ID that was serialized as this number.
+    ///
+    /// Returns a placeholder ID if this process never serialized a device ID as this number.
+    pub fn device_id(self) -> DeviceId {
+        let device_ids = DEVICE_IDS.lock().unwrap_or_else(PoisonError::into_inner);
+        match device_ids.iter().find(|&&(known, _)| known == self) {
+            Some(&(_, device_id)) => device_id,
+
+            // SAFETY: The dummy ID is only compared against other device IDs.
+            None => unsafe { DeviceId::dummy() },
+        }
+    }
+}
+
+impl From<DeviceId> for SerializedDeviceId {
+    fn from(device_id: DeviceId) -> Self {
+        Self::new(&device_id)
+    }
+}
+
+/// The 64-bit FNV-1a hash function.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A `DeviceId`, as a [`SerializedDeviceId`].
+pub(crate) mod device_id {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        device_id: &DeviceId,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        SerializedDeviceId::new(device_id).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DeviceId, D::Error> {
+        SerializedDeviceId::deserialize(deserializer).map(SerializedDeviceId::device_id)
+    }
+}
+
+/// `WindowButtons`, as an object with a flag for each button.
+pub(crate) mod window_buttons {
+    use super::*;
+
+    use winit::window::WindowButtons;
+
+    #[derive(Serialize, Deserialize)]
+    struct Buttons {
+        close: bool,
+        minimize: bool,
+        maximize: bool,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        buttons: &WindowButtons,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Buttons {
+            close: buttons.contains(WindowButtons::CLOSE),
+            minimize: buttons.contains(WindowButtons::MINIMIZE),
+            maximize: buttons.contains(WindowButtons::MAXIMIZE),
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<WindowButtons, D::Error> {
+        let Buttons {
+            close,
+            minimize,
+            maximize,
+        } = Buttons::deserialize(deserializer)?;
+
+        let mut buttons = WindowButtons::empty();
+        buttons.set(WindowButtons::CLOSE, close);
+        buttons.set(WindowButtons::MINIMIZE, minimize);
+        buttons.set(WindowButtons::MAXIMIZE, maximize);
+        Ok(buttons)
+    }
+}
+
+/// `WindowLevel`, as the name of the level.
+pub(crate) mod window_level {
+    use super::*;
+
+    use winit::window::WindowLevel;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "WindowLevel")]
+    enum Level {
+        AlwaysOnBottom,
+        Normal,
+        AlwaysOnTop,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        level: &WindowLevel,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Level::serialize(level, serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<WindowLevel, D::Error> {
+        Level::deserialize(deserializer)
+    }
+}
+
+/// An `Option<Fullscreen>`, as whether the window is borderless fullscreen on its current monitor.
+///
+/// Exclusive fullscreen and specific monitors can't be represented, and are serialized as `true`.
+pub(crate) mod fullscreen {
+    use super::*;
+
+    use winit::window::Fullscreen;
+
+    pub(crate) fn serialize<S: Serializer>(
+        fullscreen: &Option<Fullscreen>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        fullscreen.is_some().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Fullscreen>, D::Error> {
+        let fullscreen = bool::deserialize(deserializer)?;
+        Ok(fullscreen.then_some(Fullscreen::Borderless(None)))
+    }
+}
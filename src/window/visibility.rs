@@ -0,0 +1,154 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Tracking whether a window is visible.
+
+use super::Window;
+use crate::sync::ThreadSafety;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_lite::{Stream, StreamExt};
+
+/// Whether a window's contents can currently be seen.
+///
+/// This is returned by [`Window::visibility_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VisibilityState {
+    /// The window may be visible to the user.
+    Visible,
+
+    /// The window is entirely hidden by other windows, or otherwise not visible.
+    ///
+    /// See [`Window::occluded`] for the platforms where this is reported.
+    Occluded,
+
+    /// The window is minimized.
+    Minimized,
+
+    /// The application is suspended.
+    ///
+    /// This takes priority over all other states.
+    Suspended,
+}
+
+impl VisibilityState {
+    /// Tell whether the window may be visible.
+    pub fn is_visible(self) -> bool {
+        self == Self::Visible
+    }
+}
+
+/// A stream of changes to the visibility of a window.
+///
+/// This is returned by [`Window::watch_visibility`]. It yields the current visibility state when
+/// it is first polled, and then every time the state changes.
+pub struct VisibilityWatch<'a, TS: ThreadSafety> {
+    /// The window to watch.
+    window: &'a Window<TS>,
+
+    /// The last version of the window's visibility that we looked at.
+    seen: Option<u64>,
+
+    /// The last state that we yielded.
+    last: Option<VisibilityState>,
+}
+
+impl<TS: ThreadSafety> Unpin for VisibilityWatch<'_, TS> {}
+
+impl<TS: ThreadSafety> Stream for VisibilityWatch<'_, TS> {
+    type Item = VisibilityState;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if this
+                .window
+                .registration
+                .poll_visibility(&mut this.seen, cx)
+                .is_pending()
+            {
+                return Poll::Pending;
+            }
+
+            // Only yield the state if it actually changed.
+            let state = this.window.visibility_state();
+            if this.last != Some(state) {
+                this.last = Some(state);
+                return Poll::Ready(Some(state));
+            }
+        }
+    }
+}
+
+impl<TS: ThreadSafety> Window<TS> {
+    /// Get whether the contents of this window can currently be seen.
+    ///
+    /// This combines the application's [`suspended`] state, whether the window is minimized and
+    /// the last [`occluded`] event of the window. Rendering tasks can use this to stop drawing
+    /// while the window can't be seen.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Wayland:** Whether the window is minimized can't be queried, so minimized windows are
+    ///   only reported if the compositor occludes them.
+    ///
+    /// Whether the window is minimized is checked on the event loop thread when the window is
+    /// resized, focused or occluded, and when [`Window::set_minimized`] or
+    /// [`Window::is_minimized`] completes.
+    ///
+    /// [`suspended`]: crate::event_loop::EventLoopWindowTarget::suspended
+    /// [`occluded`]: Window::occluded
+    pub fn visibility_state(&self) -> VisibilityState {
+        if self.reactor.is_suspended() {
+            VisibilityState::Suspended
+        } else if self.registration.is_minimized() {
+            VisibilityState::Minimized
+        } else if self.registration.is_occluded() {
+            VisibilityState::Occluded
+        } else {
+            VisibilityState::Visible
+        }
+    }
+
+    /// Get a stream of changes to the visibility of this window.
+    ///
+    /// See [`Window::visibility_state`] for more information.
+    pub fn watch_visibility(&self) -> VisibilityWatch<'_, TS> {
+        VisibilityWatch {
+            window: self,
+            seen: None,
+            last: None,
+        }
+    }
+
+    /// Wait until the contents of this window may be visible.
+    ///
+    /// This returns immediately if the window is already visible.
+    pub async fn until_visible(&self) {
+        let mut watch = self.watch_visibility();
+
+        while let Some(state) = watch.next().await {
+            if state.is_visible() {
+                return;
+            }
+        }
+    }
+}
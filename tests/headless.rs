@@ -0,0 +1,82 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Runs windows on the headless backend from start to finish.
+
+use async_winit::dpi::PhysicalSize;
+use async_winit::event::WindowEvent;
+use async_winit::event_loop::EventLoopBuilder;
+use async_winit::filter::ReturnOrFinish;
+use async_winit::platform::run_return::EventLoopExtRunOnDemand;
+use async_winit::window::{VisibilityState, Window};
+use async_winit::ThreadUnsafe;
+
+use futures_lite::future;
+
+#[test]
+fn headless_window() {
+    let mut event_loop = EventLoopBuilder::new().build_headless::<ThreadUnsafe>();
+
+    let output = event_loop.block_on_demand(async {
+        let window = Window::<ThreadUnsafe>::new().await.unwrap();
+
+        // Operations are applied to the in-memory window.
+        window.set_title("Hello").await;
+        window.set_visible(false).await;
+        let headless = window.headless_window().unwrap();
+        assert_eq!(headless.title(), "Hello");
+        assert_eq!(headless.is_visible(), Some(false));
+        assert_eq!(window.title().await, "Hello");
+
+        // Injected events reach the handlers and update the window's state.
+        let (size, ()) = future::zip(
+            window.resized().wait(),
+            window.inject(WindowEvent::Resized(PhysicalSize::new(320, 240))),
+        )
+        .await;
+        assert_eq!(size, PhysicalSize::new(320, 240));
+        assert_eq!(window.inner_size().await, PhysicalSize::new(320, 240));
+
+        let (focused, ()) = future::zip(
+            window.focused().wait(),
+            window.inject(WindowEvent::Focused(true)),
+        )
+        .await;
+        assert!(focused);
+        assert!(window.has_focus());
+
+        // Minimizing the window is reflected in its visibility.
+        window.set_minimized(true).await;
+        assert_eq!(window.visibility_state(), VisibilityState::Minimized);
+
+        // So is restoring it outside of the application, once an event arrives.
+        headless.set_minimized(false);
+        window
+            .inject(WindowEvent::Resized(PhysicalSize::new(320, 240)))
+            .await;
+        assert_eq!(window.visibility_state(), VisibilityState::Visible);
+
+        window.request_redraw();
+        headless.redraw_requests()
+    });
+
+    match output {
+        ReturnOrFinish::FutureReturned(redraws) => assert_eq!(redraws, 1),
+        ReturnOrFinish::Output(_) => panic!("the event loop exited before the test finished"),
+    }
+}
@@ -0,0 +1,106 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Runs tests with `#[async_winit::test]` on the headless backend.
+
+use async_winit::event::{ElementState, KeyEvent, WindowEvent};
+use async_winit::event_loop::EventLoopWindowTarget;
+use async_winit::keyboard::{Key, KeyCode, PhysicalKey};
+use async_winit::window::Window;
+use async_winit::DefaultThreadSafety;
+
+use futures_lite::future;
+
+/// Focus a new window, checking that the reactor was reset since the last test.
+async fn focus_new_window(target: &EventLoopWindowTarget) {
+    // A reactor left over from another test would remember its focused window.
+    assert_eq!(target.focused_window(), None);
+
+    let window = Window::<DefaultThreadSafety>::new().await.unwrap();
+    window.inject(WindowEvent::Focused(true)).await;
+    assert_eq!(target.focused_window(), Some(window.id()));
+
+    // Neither would a reactor left over from another test be able to run after this.
+    target.set_exit();
+}
+
+#[async_winit::test]
+async fn reactor_is_reset(target: EventLoopWindowTarget) {
+    focus_new_window(&target).await;
+}
+
+#[async_winit::test]
+async fn reactor_is_reset_again(target: EventLoopWindowTarget) {
+    focus_new_window(&target).await;
+}
+
+#[async_winit::test]
+async fn returns_output() -> Result<(), String> {
+    let window = Window::<DefaultThreadSafety>::new()
+        .await
+        .map_err(|err| err.to_string())?;
+    window.set_title("Hello").await;
+    assert_eq!(window.title().await, "Hello");
+    Ok(())
+}
+
+#[async_winit::test]
+#[should_panic(expected = "boom")]
+async fn propagates_panics() {
+    let _window = Window::<DefaultThreadSafety>::new().await.unwrap();
+    panic!("boom");
+}
+
+#[async_winit::test(timeout = 1)]
+#[should_panic(expected = "test `times_out` timed out after 1s")]
+async fn times_out() {
+    future::pending::<()>().await;
+}
+
+#[async_winit::test]
+async fn dropped_window_is_removed(target: EventLoopWindowTarget) {
+    let window = Window::<DefaultThreadSafety>::new().await.unwrap();
+    window.inject(WindowEvent::Focused(true)).await;
+    assert_eq!(target.focused_window(), Some(window.id()));
+
+    drop(window);
+    assert_eq!(target.focused_window(), None);
+}
+
+#[async_winit::test]
+async fn injects_keys(target: EventLoopWindowTarget) {
+    let window = Window::<DefaultThreadSafety>::new().await.unwrap();
+    let event = KeyEvent::new(
+        PhysicalKey::Code(KeyCode::KeyA),
+        Key::Character("a".into()),
+        ElementState::Pressed,
+    );
+
+    let (input, ((window_id, any_input), ())) = future::zip(
+        window.keyboard_input().wait(),
+        future::zip(
+            target.any_keyboard_input().wait(),
+            window.inject_key(event.clone()),
+        ),
+    )
+    .await;
+    assert_eq!(input.event, event);
+    assert!(input.is_synthetic);
+    assert_eq!(window_id, window.id());
+    assert_eq!(any_input.event, event);
+}
@@ -0,0 +1,182 @@
+/*
+
+`async-winit` is free software: you can redistribute it and/or modify it under the terms of one of
+the following licenses:
+
+* GNU Lesser General Public License as published by the Free Software Foundation, either
+  version 3 of the License, or (at your option) any later version.
+* Mozilla Public License as published by the Mozilla Foundation, version 2.
+
+`async-winit` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General
+Public License and the Patron License for more details.
+
+You should have received a copy of the GNU Lesser General Public License and the Mozilla
+Public License along with `async-winit`. If not, see <https://www.gnu.org/licenses/>.
+
+*/
+
+//! Runs timers on a headless event loop driven by a `ManualClock`.
+//!
+//! Only one reactor can exist per process, so this runs every case in turn on the same event
+//! loop instead of using the default test harness.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Wake, Waker};
+use std::time::Duration;
+
+use async_winit::event_loop::{EventLoop, EventLoopBuilder, EventLoopWindowTarget, LifecycleEvent};
+use async_winit::filter::ReturnOrFinish;
+use async_winit::platform::run_return::EventLoopExtRunOnDemand;
+use async_winit::{ManualClock, MissedTickBehavior, ThreadUnsafe, Timer};
+
+use futures_lite::future;
+
+fn main() {
+    let clock = ManualClock::new();
+    let mut event_loop = EventLoopBuilder::new()
+        .with_clock(clock.clone())
+        .build_headless::<ThreadUnsafe>();
+
+    let target = event_loop.window_target().clone();
+
+    run(
+        &mut event_loop,
+        "fires_at_deadline",
+        fires_at_deadline(&clock),
+    );
+    run(
+        &mut event_loop,
+        "fires_after_instant_suspension",
+        fires_after_instant_suspension(&clock, &target),
+    );
+    run(
+        &mut event_loop,
+        "skips_after_long_stall",
+        skips_after_long_stall(&clock),
+    );
+    run(
+        &mut event_loop,
+        "fires_beyond_wheel_span",
+        fires_beyond_wheel_span(&clock),
+    );
+}
+
+/// Run a single case on the event loop.
+fn run(event_loop: &mut EventLoop<ThreadUnsafe>, name: &str, case: impl Future<Output = ()>) {
+    print!("test {name} ... ");
+    match event_loop.block_on_demand(case) {
+        ReturnOrFinish::FutureReturned(()) => println!("ok"),
+        ReturnOrFinish::Output(_) => panic!("the event loop exited during `{name}`"),
+    }
+}
+
+/// Wait for a timer that should fire within a few iterations of the event loop.
+async fn expect_fire(timer: &mut Timer<ThreadUnsafe>) {
+    future::or(
+        async {
+            timer.await;
+        },
+        async {
+            for _ in 0..100 {
+                future::yield_now().await;
+            }
+            panic!("the timer didn't fire");
+        },
+    )
+    .await
+}
+
+/// A waker that counts how many times it was woken up.
+struct WakeCounter(AtomicUsize);
+
+impl WakeCounter {
+    /// Wait until the waker was woken up `count` times in total.
+    async fn wait_for(&self, count: usize) {
+        for _ in 0..100 {
+            if self.0.load(Ordering::SeqCst) >= count {
+                return;
+            }
+            future::yield_now().await;
+        }
+        panic!("the timer wasn't woken up");
+    }
+}
+
+impl Wake for WakeCounter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// A timer fires once the clock reaches its deadline exactly.
+async fn fires_at_deadline(clock: &ManualClock) {
+    let mut timer = Timer::after(Duration::from_secs(5));
+    assert!(future::poll_once(&mut timer).await.is_none());
+
+    clock.advance(Duration::from_secs(5));
+    expect_fire(&mut timer).await;
+}
+
+/// A paused timer fires after the application is suspended and resumed without the clock moving.
+async fn fires_after_instant_suspension(
+    clock: &ManualClock,
+    target: &EventLoopWindowTarget<ThreadUnsafe>,
+) {
+    // Poll the timer with the same waker throughout, and never while waiting for it.
+    let counter = Arc::new(WakeCounter(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut timer = Timer::<ThreadUnsafe>::after(Duration::from_secs(5)).pause_while_suspended();
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+    target.inject_lifecycle(LifecycleEvent::Suspended).await;
+    target.inject_lifecycle(LifecycleEvent::Resumed).await;
+
+    // Being resumed wakes up the timer so that it can reschedule itself.
+    counter.wait_for(1).await;
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+    clock.advance(Duration::from_secs(5));
+    counter.wait_for(2).await;
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_ready());
+}
+
+/// An interval timer that skips missed ticks keeps ticking after missing billions of them.
+async fn skips_after_long_stall(clock: &ManualClock) {
+    let mut timer = Timer::<ThreadUnsafe>::interval(Duration::from_millis(1));
+    timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    clock.advance(Duration::from_secs(60 * 60 * 24 * 60));
+    expect_fire(&mut timer).await;
+
+    clock.advance(Duration::from_millis(1));
+    expect_fire(&mut timer).await;
+}
+
+/// A timer that is further in the future than the timer wheel spans fires at its deadline.
+async fn fires_beyond_wheel_span(clock: &ManualClock) {
+    const THREE_YEARS: Duration = Duration::from_secs(60 * 60 * 24 * 365 * 3);
+
+    let counter = Arc::new(WakeCounter(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut timer = Timer::<ThreadUnsafe>::after(THREE_YEARS);
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+    clock.advance(THREE_YEARS - Duration::from_secs(1));
+    for _ in 0..100 {
+        future::yield_now().await;
+    }
+    let woken = counter.0.load(Ordering::SeqCst);
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+    clock.advance(Duration::from_secs(1));
+    counter.wait_for(woken + 1).await;
+    assert!(Pin::new(&mut timer).poll(&mut cx).is_ready());
+}